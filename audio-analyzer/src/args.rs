@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use audio_player::Downmix;
+use clap::{Parser, ValueEnum};
 
 #[derive(Parser)]
 #[command(name="J.Garnier", author, version, about, long_about = None)]
@@ -13,6 +14,10 @@ pub struct Cli {
     #[arg(short, long, default_value = "SpecFlux")]
     pub beat_detection: String,
 
+    /// Plotted channels
+    #[arg(short, long, value_enum, default_value_t = Channels::Average)]
+    pub channels: Channels,
+
     /// Display static spectrum
     #[arg(short, long, conflicts_with_all=&["save_spectrum", "output", "live_visualization"])]
     pub visualization: bool,
@@ -29,3 +34,34 @@ pub struct Cli {
     #[arg(short, long, requires = "save_action", group = "output_param", conflicts_with_all=&["live_visualization", "visualization"])]
     pub output: Option<PathBuf>,
 }
+
+/// Channels plotted, selected by --channels
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Channels {
+    /// Left channel
+    Left,
+    /// Right channel, left one for mono files
+    Right,
+    /// Half sum of left & right channels
+    Mid,
+    /// Half difference of left & right channels
+    Side,
+    /// Mean of every channel
+    Average,
+    /// Left & right channels overlaid
+    Both,
+}
+
+impl Channels {
+    /// Mix of the plotted channels, none if they are all plotted
+    pub fn downmix(self) -> Option<Downmix> {
+        match self {
+            Channels::Left => Some(Downmix::Left),
+            Channels::Right => Some(Downmix::Right),
+            Channels::Mid => Some(Downmix::Mid),
+            Channels::Side => Some(Downmix::Side),
+            Channels::Average => Some(Downmix::Average),
+            Channels::Both => None,
+        }
+    }
+}
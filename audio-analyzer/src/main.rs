@@ -1,13 +1,11 @@
-use audio_player::{MetadataParserBuilder, MetadataParserWrapper};
+use audio_player::{Downmix, MetadataParserBuilder, MetadataParserWrapper};
 use std::path::PathBuf;
 
 use std::path::Path;
-use std::sync::{Arc, Mutex};
 
 mod pixel_buf;
 mod spectrum_analyzer;
 use crate::spectrum_analyzer::*;
-use audio_player::playTrack;
 
 use std::thread::{self};
 
@@ -51,15 +49,17 @@ fn run(args: &Cli) -> Result<i32, Box<dyn std::error::Error>> {
         "SpecFlux" => aubio_rs::OnsetMode::SpecFlux,
         _ => aubio_rs::OnsetMode::SpecFlux,
     };
+    // Both channels are overlaid, otherwise mixed into one
+    let downmix = args.channels.downmix();
     let beats = get_wav_beats(music_path, beat_algo);
     if args.visualization {
-        static_spectrum_display(music_path, beats, metadata_parser);
+        static_spectrum_display(music_path, beats, downmix, metadata_parser);
     } else if args.live_visualization {
-        live_spectrum_display(music_path, beats, metadata_parser);
+        live_spectrum_display(music_path, beats, downmix, metadata_parser);
         return Ok(1);
     } else if args.save_spectrum {
         if let Some(output_path) = args.output.as_deref() {
-            save_as_png(music_path, output_path, beats, metadata_parser);
+            save_as_png(music_path, output_path, beats, downmix, metadata_parser);
             return Ok(1);
         }
     }
@@ -106,10 +106,11 @@ fn get_wav_beats(music_path: &Path, beat_algo: aubio_rs::OnsetMode) -> Vec<f64>
 fn static_spectrum_display(
     music_path: &Path,
     beats: Vec<f64>,
+    downmix: Option<Downmix>,
     metadata_parser: Box<dyn MetadataParserWrapper>,
 ) {
     if let Some((samples_formatted, beats_formatted, _frame_rate, _channel_nb, _frame_number)) =
-        analyze_samples(&metadata_parser, music_path, &beats)
+        analyze_samples(&metadata_parser, music_path, &beats, downmix)
     {
         draw_static_into_window(music_path, &samples_formatted, &beats_formatted).unwrap();
     }
@@ -118,12 +119,18 @@ fn static_spectrum_display(
 fn live_spectrum_display(
     music_path: &Path,
     beats: Vec<f64>,
+    downmix: Option<Downmix>,
     metadata_parser: Box<dyn MetadataParserWrapper>,
 ) {
-    if let Some((samples_formatted, beats_formatted, _frame_rate, _channel_nb, _frame_number)) =
-        analyze_samples(&metadata_parser, music_path, &beats)
+    if let Some((samples_formatted, beats_formatted, frame_rate, _channel_nb, _frame_number)) =
+        analyze_samples(&metadata_parser, music_path, &beats, downmix)
     {
-        live_play(music_path.to_path_buf(), samples_formatted, beats_formatted);
+        live_play(
+            music_path.to_path_buf(),
+            samples_formatted,
+            beats_formatted,
+            frame_rate,
+        );
     }
 }
 
@@ -131,46 +138,30 @@ fn save_as_png(
     music_path: &Path,
     output_path: &Path,
     beats: Vec<f64>,
+    downmix: Option<Downmix>,
     metadata_parser: Box<dyn MetadataParserWrapper>,
 ) {
     if let Some((samples_formatted, beats_formatted, _frame_rate, _channel_nb, _frame_number)) =
-        analyze_samples(&metadata_parser, music_path, &beats)
+        analyze_samples(&metadata_parser, music_path, &beats, downmix)
     {
         draw_into_png(output_path, &samples_formatted, &beats_formatted).unwrap();
     }
 }
 
-fn live_play(music_path: PathBuf, samples_formatted: Box<Vec<f32>>, beats_formatted: Vec<f64>) {
-    let music_path_1 = music_path.clone();
-    let music_path_2 = music_path.clone();
-    let sound_sync = Arc::new(Mutex::new(false));
-    let ts_sound_sync = Arc::new(Mutex::new((0_u64, (0_u64, 0_u64, 0_f64))));
-    let sound_sync_listener = Arc::clone(&sound_sync);
-    let ts_sync_emit = Arc::clone(&ts_sound_sync);
-    let player_t = thread::spawn(move || {
-        playTrack(
-            music_path_1.as_path(),
-            ts_sync_emit,
-            Arc::clone(&sound_sync_listener),
-        )
-        .unwrap();
-        let mut sound_sync_finished = sound_sync_listener
-            .try_lock()
-            .expect("couldn't lock at end of play");
-        *sound_sync_finished = false;
-    });
-    let sound_sync_launcher = Arc::clone(&sound_sync);
-    let ts_sync_listen = Arc::clone(&ts_sound_sync);
+fn live_play(
+    music_path: PathBuf,
+    samples_formatted: Vec<Vec<f32>>,
+    beats_formatted: Vec<f64>,
+    inverse_sample_rate: f64,
+) {
     let windows_t = thread::spawn(move || {
         draw_live_cursor_into_window(
-            music_path_2.as_path(),
+            music_path.as_path(),
             &samples_formatted,
             &beats_formatted,
-            ts_sync_listen,
-            sound_sync_launcher,
+            inverse_sample_rate,
         )
         .unwrap();
     });
-    player_t.join().unwrap();
     windows_t.join().unwrap();
 }
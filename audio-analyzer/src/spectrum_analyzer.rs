@@ -1,10 +1,11 @@
 /// TODO clean all this mess
 use std::borrow::{Borrow, BorrowMut};
 use std::path::Path;
-use std::sync::{Arc, Mutex};
 
 use crate::pixel_buf::PixelBuf;
-use audio_player::{AudioTag, MetadataParserWrapper};
+use audio_player::{
+    AudioError, AudioTag, Downmix, MetadataParserWrapper, PlaybackOptions, Player, SampleLayout,
+};
 use minifb::{Key, Window, WindowOptions};
 use plotters::backend::{BGRXPixel, PixelFormat};
 use plotters::chart::ChartState;
@@ -13,18 +14,21 @@ use plotters::coord::Shift;
 use plotters::drawing::IntoDrawingArea;
 use plotters::prelude::*;
 use plotters::series::LineSeries;
-use plotters::style::{BLUE, RED};
+use plotters::style::{BLUE, MAGENTA, RED};
 use std::ops::Range;
 
 const WIDTH: usize = 1024;
 const HEIGHT: usize = 768;
+/// One colour per plotted channel
+const SERIES_COLORS: [RGBColor; 2] = [RED, MAGENTA];
 
 pub fn analyze_samples(
     metadata_parser: &Box<dyn MetadataParserWrapper>,
     music_path: &Path,
     beats: &Vec<f64>,
-) -> Option<(Box<Vec<f32>>, Vec<f64>, f64, u32, u64)> {
-    let tag_values_res = metadata_parser.get_metadata_string(
+    downmix: Option<Downmix>,
+) -> Option<(Vec<Vec<f32>>, Vec<f64>, f64, u32, u64)> {
+    let tag_values_res = metadata_parser.get_metadata(
         music_path,
         &vec![
             AudioTag::FrameRate,
@@ -41,14 +45,35 @@ pub fn analyze_samples(
             rate, channel_c, frame_nb
         );
 
-        if let Some(samples) = metadata_parser.get_file_samples(music_path) {
-            println!("samples to print {}", samples.len());
-            let inverse_sample_rate = 1.0 / rate.parse::<f64>().unwrap();
-            let channel_nb = channel_c.parse::<u32>().unwrap();
-            let frame_nb_number = frame_nb.parse::<u64>().unwrap();
+        if let Ok(mut stream) = metadata_parser.get_sample_stream(music_path, SampleLayout::Planar)
+        {
+            // Without downmix, first two channels are drawn, one for mono files.
+            let mut samples_formatted: Vec<Vec<f32>> = Vec::new();
+            let decoded = stream.try_for_each(|block| {
+                let block = block?;
+                let series = match downmix {
+                    Some(downmix) => vec![block.downmix(downmix)],
+                    None => block.to_planar().into_iter().take(2).collect(),
+                };
+                samples_formatted.resize_with(series.len().max(samples_formatted.len()), Vec::new);
+                for (samples, block_samples) in samples_formatted.iter_mut().zip(series) {
+                    samples.extend(block_samples);
+                }
+                Ok::<(), AudioError>(())
+            });
+            // A truncated plot would look like a valid one, decoding errors abort it.
+            if let Err(err) = decoded {
+                eprintln!("decoding error for {:?}: {}", music_path, err);
+                return None;
+            }
+            let samples_len = samples_formatted.first().map_or(0, Vec::len);
+            println!("samples to print {}", samples_len);
+            let inverse_sample_rate = 1.0 / rate.as_f64().unwrap();
+            let channel_nb = channel_c.as_i64().unwrap() as u32;
+            let frame_nb_number = frame_nb.as_i64().unwrap() as u64;
             println!(
                 "samples length {} at frame rate is {}",
-                (samples.len() as f64) * inverse_sample_rate / channel_nb as f64,
+                (samples_len as f64) * inverse_sample_rate,
                 inverse_sample_rate
             );
             let beats_formatted: Vec<f64> = beats
@@ -62,19 +87,6 @@ pub fn analyze_samples(
                 beats_formatted.first(),
                 beats_formatted.last()
             );
-            let samples_formatted: Box<Vec<f32>> = Box::new(
-                samples
-                    .iter()
-                    .enumerate()
-                    .filter(|(i, v)| *i as f64 % channel_nb as f64 == 0.0)
-                    .map(|(i, v)| *v)
-                    .collect(),
-            );
-            println!(
-                "sample format : {}, {}",
-                samples.len(),
-                samples_formatted.len()
-            );
             return Some((
                 samples_formatted,
                 beats_formatted,
@@ -87,20 +99,20 @@ pub fn analyze_samples(
     None
 }
 
+fn samples_ranges(audio_samples: &[Vec<f32>]) -> Option<(Range<f64>, Range<f64>)> {
+    let x_max = audio_samples.iter().map(Vec::len).max()? as f64;
+    let samples = audio_samples.iter().flatten().copied();
+    let y_min = samples.clone().reduce(f32::min)?;
+    let y_max = samples.reduce(f32::max)?;
+    Some((0.0..x_max, y_min as f64..y_max as f64))
+}
+
 pub fn draw_static_into_window(
     _music_path: &Path,
-    audio_samples: &Box<Vec<f32>>,
+    audio_samples: &[Vec<f32>],
     beats: &Vec<f64>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let x_max = audio_samples.len() as f64;
-    let x_min = 0.0;
-    let x_range = x_min..x_max;
-    if let (Some(y_min), Some(y_max)) = (
-        audio_samples.iter().map(|v| *v).reduce(f32::min),
-        audio_samples.iter().map(|v| *v).reduce(f32::max),
-    ) {
-        let y_range = y_min as f64..y_max as f64;
-
+    if let Some((x_range, y_range)) = samples_ranges(audio_samples) {
         let (mut window, mut pixel_buf, _chart_state) = setup_window();
 
         println!("done into drawing");
@@ -122,20 +134,12 @@ pub fn draw_static_into_window(
 }
 
 pub fn draw_live_cursor_into_window(
-    _music_path: &Path,
-    audio_samples: &Box<Vec<f32>>,
+    music_path: &Path,
+    audio_samples: &[Vec<f32>],
     beats: &Vec<f64>,
-    live_sample: Arc<Mutex<(u64, (u64, u64, f64))>>,
-    start_play: Arc<Mutex<bool>>,
+    inverse_sample_rate: f64,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let x_max = audio_samples.len() as f64;
-    let x_min = 0.0;
-    let x_range = x_min..x_max;
-    if let (Some(y_min), Some(y_max)) = (
-        audio_samples.iter().map(|v| *v).reduce(f32::min),
-        audio_samples.iter().map(|v| *v).reduce(f32::max),
-    ) {
-        let y_range = y_min as f64..y_max as f64;
+    if let Some((x_range, y_range)) = samples_ranges(audio_samples) {
         let (mut window, mut pixel_buf, chart_state) = setup_window();
         generete_spectrum_and_beat(
             pixel_buf.borrow_mut(),
@@ -146,40 +150,38 @@ pub fn draw_live_cursor_into_window(
         )
         .expect("Unable to create spectrum buffer");
         println!("done into drawing");
-        {
-            let mut starting_play = start_play.lock().expect("unable to acquire play lock");
-            *starting_play = true;
-        }
 
+        // Player stops as soon as it is dropped, i.e when window is closed
+        let player = Player::new(music_path, PlaybackOptions::default());
         println!("go for play");
         while window.is_open() && !window.is_key_down(Key::Escape) {
-            // using a channel will create a delay as time passes
-            let (idx, (h, m, s)) = {
-                let sync_play = live_sample
-                    .try_lock()
-                    .expect("Coulnd't lock read play sync");
-                let (idx, (h, m, s)) = (sync_play.0, sync_play.1);
-                (idx, (h, m, s))
-            };
+            let position = player.position();
+            let idx = (position.as_secs_f64() / inverse_sample_rate) as usize;
 
-            if idx as usize >= audio_samples.len() {
+            if idx as f64 >= x_range.end {
                 println!("Still receiving but idx is out of audio bounds");
                 break;
             }
-            println!("{}, {}:{:0>2}:{:0>4.1}", idx, h, m, s);
+            let seconds = position.as_secs();
+            println!(
+                "{}, {}:{:0>2}:{:0>4.1}",
+                idx,
+                seconds / (60 * 60),
+                (seconds % (60 * 60)) / 60,
+                (seconds % 60) as f64 + position.subsec_millis() as f64 / 1000.0
+            );
             generate_live_cursor_spectrum(
                 pixel_buf.borrow_mut(),
                 &chart_state,
                 &x_range,
                 &y_range,
-                idx as usize,
+                idx,
             )?;
 
             window
                 .update_with_buffer(pixel_buf.borrow(), WIDTH, HEIGHT)
                 .unwrap();
-            // }
-            if *(start_play.lock().expect("unable to acquire play lock")) == false {
+            if player.is_finished() {
                 println!("Audio finished");
                 break;
             }
@@ -242,7 +244,7 @@ fn get_drawing_area(pixel_buf: &mut [u8]) -> DrawingArea<BitMapBackend<BGRXPixel
 
 pub fn draw_into_png(
     output_img_path: &Path,
-    audio_samples: &Box<Vec<f32>>,
+    audio_samples: &[Vec<f32>],
     beats: &Vec<f64>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let point_diam: u32 = 1;
@@ -251,14 +253,7 @@ pub fn draw_into_png(
     let step_length = (beats.len() as f64) * smallest_step.round();
     let root =
         BitMapBackend::new(output_img_path.to_str().unwrap(), (2048, 768)).into_drawing_area();
-    let x_max = audio_samples.len() as f64;
-    let x_min = 0.0;
-    let x_range = x_min..x_max;
-    if let (Some(y_min), Some(y_max)) = (
-        audio_samples.iter().map(|v| *v).reduce(f32::min),
-        audio_samples.iter().map(|v| *v).reduce(f32::max),
-    ) {
-        let y_range = y_min as f64..y_max as f64;
+    if let Some((x_range, y_range)) = samples_ranges(audio_samples) {
         generate_static_spectrum(&root, audio_samples, beats, &x_range, &y_range)?;
     }
     // To avoid the IO failure being ignored silently, we manually call the present function
@@ -274,7 +269,7 @@ fn generete_spectrum_and_beat(
     buff: &mut [u8],
     x_range: &Range<f64>,
     y_range: &Range<f64>,
-    audio_samples: &Box<Vec<f32>>,
+    audio_samples: &[Vec<f32>],
     beats: &Vec<f64>,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let root =
@@ -295,15 +290,7 @@ fn generete_spectrum_and_beat(
         .draw()
         .expect("Could not draw initial chart");
     // drawing spectrum
-    chart
-        .draw_series(LineSeries::new(
-            audio_samples
-                .iter()
-                .enumerate()
-                .map(|(x, y)| (x as f64, *y as f64)),
-            &RED,
-        ))
-        .expect("could not draw spectrum series");
+    draw_samples_series(&mut chart, audio_samples);
 
     // drawing beats
     chart
@@ -365,7 +352,7 @@ fn generate_live_cursor_spectrum(
 
 fn generate_static_spectrum<T>(
     root: &DrawingArea<BitMapBackend<T>, Shift>,
-    audio_samples: &Box<Vec<f32>>,
+    audio_samples: &[Vec<f32>],
     beats: &Vec<f64>,
     x_range: &Range<f64>,
     y_range: &Range<f64>,
@@ -387,15 +374,7 @@ where
         .draw()
         .expect("Could not draw initial chart");
 
-    chart
-        .draw_series(LineSeries::new(
-            audio_samples
-                .iter()
-                .enumerate()
-                .map(|(x, y)| (x as f64, *y as f64)),
-            &RED,
-        ))
-        .expect("could not draw spectrum series");
+    draw_samples_series(&mut chart, audio_samples);
     chart
         .draw_series(beats.iter().map(|t| {
             Polygon::new(
@@ -407,6 +386,23 @@ where
     return Ok(());
 }
 
+fn draw_samples_series<DB: DrawingBackend>(
+    chart: &mut ChartContext<DB, Cartesian2d<RangedCoordf64, RangedCoordf64>>,
+    audio_samples: &[Vec<f32>],
+) {
+    for (samples, color) in audio_samples.iter().zip(SERIES_COLORS.iter()) {
+        chart
+            .draw_series(LineSeries::new(
+                samples
+                    .iter()
+                    .enumerate()
+                    .map(|(x, y)| (x as f64, *y as f64)),
+                color,
+            ))
+            .expect("could not draw spectrum series");
+    }
+}
+
 fn find_smallest_step<T>(samples: &[T]) -> Option<T>
 where
     T: core::cmp::PartialOrd<T> + std::ops::Sub<Output = T> + std::fmt::Display + std::marker::Copy,
@@ -1,9 +1,14 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
-use clap::Parser;
+use audio_player::time_parser::parse_time;
+use audio_player::TagEdit;
+use clap::{ArgGroup, Parser};
 
 #[derive(Parser)]
 #[command(name="J.Garnier", author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("tag_edit").args(["set_tag", "remove_tag"]).multiple(true).requires("edit_scope")))]
+#[command(group(ArgGroup::new("edit_scope").args(["all", "filter_tag"])))]
 pub struct Cli {
     /// Audio folder in absolute path to parse recursively
     #[arg(short, long, required = true)]
@@ -26,4 +31,48 @@ pub struct Cli {
     /// Is case sensitive
     #[arg(short, long, group = "filter_val", requires = "filter", conflicts_with_all=&["aggregate_tag", "count"])]
     pub filter_tag: Option<String>,
+
+    /// Computes EBU R128 loudness and ReplayGain values, each folder being an album.
+    /// May take several minutes to process.
+    #[arg(long, conflicts_with_all=&["aggregate_tag", "count", "filter_tag"])]
+    pub loudness_scan: bool,
+
+    /// Writes embedded pictures of each album into given folder, named by usage i.e "front-cover.jpg".
+    /// Album folders are recreated relative to --input.
+    #[arg(long, conflicts_with_all=&["aggregate_tag", "count", "filter_tag"])]
+    pub extract_covers: Option<PathBuf>,
+
+    /// Lists audio files whose extension names another format than their content one, i.e a FLAC named ".mp3"
+    #[arg(long, conflicts_with_all=&["aggregate_tag", "count", "filter_tag"])]
+    pub check_formats: bool,
+
+    /// Groups audio files holding a same recording, whatever their format or bitrate, by acoustic fingerprint.
+    /// May take several minutes to process.
+    #[arg(long, conflicts_with_all=&["aggregate_tag", "count", "filter_tag"])]
+    pub find_duplicates: bool,
+
+    /// Similarity from 0 to 1 files found by --find-duplicates share, unrelated files being around 0.5
+    #[arg(long, default_value_t = 0.85, requires = "find_duplicates")]
+    pub min_similarity: f64,
+
+    /// Audio fingerprinted by --find-duplicates from the start of each file, as seconds or mm:ss i.e ["30", "2:00"]
+    #[arg(long, default_value = "120", requires = "find_duplicates", value_parser = parse_time)]
+    pub fingerprint_length: Duration,
+
+    /// Sets a tag on MP3 & FLAC files i.e "genre=Jazz", cumulative, applied in command line order with --remove-tag.
+    /// Only files matching --filter-tag (-f) and --filter-value are edited, every file with --all.
+    #[arg(long, value_parser = TagEdit::parse_set, conflicts_with_all=&["aggregate_tag", "count", "loudness_scan", "extract_covers", "check_formats", "find_duplicates"])]
+    pub set_tag: Vec<TagEdit>,
+
+    /// Removes a tag from MP3 & FLAC files i.e "comment", cumulative. Filtered as --set-tag
+    #[arg(long, value_parser = TagEdit::parse_remove, conflicts_with_all=&["aggregate_tag", "count", "loudness_scan", "extract_covers", "check_formats", "find_duplicates"])]
+    pub remove_tag: Vec<TagEdit>,
+
+    /// Edits every file with --set-tag & --remove-tag, required without --filter-tag (-f)
+    #[arg(long, requires = "tag_edit")]
+    pub all: bool,
+
+    /// Lists files --set-tag & --remove-tag would edit, without writing them
+    #[arg(long, requires = "tag_edit")]
+    pub dry_run: bool,
 }
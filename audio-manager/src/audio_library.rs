@@ -1,3 +1,11 @@
+use audio_player::AudioError;
+use std::path::PathBuf;
+
+pub mod cover_extractor;
+pub mod loudness_scan;
 pub mod metadata_aggregator;
 mod tags_aggregator_result_builder;
 pub mod visitor;
+
+/// Audio files or album folders which couldn't be processed, by path
+pub type PathErrors = Vec<(PathBuf, AudioError)>;
@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use audio_player::MetadataParserWrapper;
+
+use crate::audio_library::{visitor, PathErrors};
+
+/// Written pictures by album folder
+pub type AlbumCovers = BTreeMap<PathBuf, Vec<PathBuf>>;
+
+/// Extracts embedded pictures of each album, from its first track holding some
+///
+/// # Arguments
+/// * `path` - Audio folder to parse recursively
+/// * `metadata_parser` - Audio file reader
+/// * `out_dir` - Destination folder, album folders are recreated relative to `path`
+///
+/// # Returns
+/// written files by album folder, and extraction errors by track, or by `path` if unreadable
+pub fn extract_album_covers(
+    path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    out_dir: &Path,
+) -> (AlbumCovers, PathErrors) {
+    let albums = match visitor::group_by_album(path) {
+        Ok(albums) => albums,
+        Err(error) => return (BTreeMap::new(), vec![(path.to_path_buf(), error.into())]),
+    };
+
+    let mut covers = AlbumCovers::new();
+    let mut errors = PathErrors::new();
+    for (album_path, tracks) in albums {
+        let album_out_dir = match album_path.strip_prefix(path) {
+            Ok(relative_path) => out_dir.join(relative_path),
+            Err(_) => out_dir.to_path_buf(),
+        };
+        for track in tracks {
+            match metadata_parser.extract_visuals(&track, &album_out_dir) {
+                Ok(written) if written.is_empty() => (),
+                Ok(written) => {
+                    covers.insert(album_path, written);
+                    break;
+                }
+                Err(error) => errors.push((track, error)),
+            }
+        }
+    }
+    (covers, errors)
+}
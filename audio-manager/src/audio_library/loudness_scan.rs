@@ -0,0 +1,35 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use audio_player::AlbumLoudness;
+
+use crate::audio_library::{visitor, PathErrors};
+
+/// Scans loudness of audio files, each folder being an album
+///
+/// # Arguments
+/// * `path` - Audio folder to parse recursively
+///
+/// # Returns
+/// albums loudness by folder, computed from the tracks that decoded, and scan errors by track,
+/// by album folder if none of its tracks decoded, or by `path` if unreadable
+pub fn scan_albums(path: &Path) -> (BTreeMap<PathBuf, AlbumLoudness>, PathErrors) {
+    let albums = match visitor::group_by_album(path) {
+        Ok(albums) => albums,
+        Err(error) => return (BTreeMap::new(), vec![(path.to_path_buf(), error.into())]),
+    };
+
+    let mut loudness = BTreeMap::new();
+    let mut errors = Vec::new();
+    for (album_path, tracks) in albums {
+        let (album_loudness, track_errors) = audio_player::scan_album(&tracks);
+        errors.extend(track_errors);
+        match album_loudness {
+            Ok(album_loudness) => {
+                loudness.insert(album_path, album_loudness);
+            }
+            Err(error) => errors.push((album_path, error)),
+        }
+    }
+    (loudness, errors)
+}
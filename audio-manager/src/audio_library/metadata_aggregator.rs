@@ -1,13 +1,24 @@
-use std::error::Error;
-use std::{collections::HashMap, fs::DirEntry, path::Path, rc::Rc};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fs::DirEntry;
+use std::path::PathBuf;
+use std::time::Duration;
+use std::{collections::HashMap, path::Path, rc::Rc};
 
-use audio_player::{AudioTag, MetadataParserWrapper};
+use audio_player::{
+    tag_writer, AudioTag, CueTrack, MetadataParserWrapper, MetadataValue, TagEdit, TagsResult,
+};
 
 use crate::audio_library::tags_aggregator_result_builder;
 use crate::audio_library::visitor;
+use crate::audio_library::PathErrors;
 
 /// Aggregate Audio files based on tags list given.
 /// For now it only accepts the first tag
+/// Numeric values are aggregated and ordered numerically, values of other types follow them
+/// (see MetadataValue::total_cmp).
+/// CUE sheet tracks are counted as separate tracks, files without this tag are not counted.
+/// Unreadable files are reported by path, the scan goes on.
 ///
 /// # Arguments
 /// * `path` - Audio folder to parse recursively
@@ -17,96 +28,269 @@ pub fn aggregate_by(
     path: &Path,
     metadata_parser: &Box<dyn MetadataParserWrapper>,
     tags: &Vec<AudioTag>,
-) -> (Rc<HashMap<String, Rc<usize>>>, Rc<Vec<Box<dyn Error>>>) {
+) -> (Rc<Vec<(MetadataValue, usize)>>, Rc<PathErrors>) {
     let tag_aggregator_model = tags_aggregator_result_builder::build_structure(tags);
-    let mut sample_aggr: Rc<HashMap<String, Rc<usize>>> = Rc::new(HashMap::new());
-    let mut errors: Rc<Vec<Box<dyn Error>>> = Rc::new(Vec::new());
+    let mut sample_aggr: BTreeMap<AggregationKey, usize> = BTreeMap::new();
+    let mut errors: Rc<PathErrors> = Rc::new(Vec::new());
 
     let mut closure_sample_aggr = {
-        let mut_map = Rc::get_mut(&mut sample_aggr).unwrap();
+        let mut_aggr = &mut sample_aggr;
         let mut_errors = Rc::get_mut(&mut errors).unwrap();
 
-        move |_dir: &DirEntry, audio_path: &Path| {
-            match metadata_parser.get_metadata_string(audio_path, tags) {
+        move |audio_path: &Path, cue_track: Option<&CueTrack>| {
+            match get_track_metadata(metadata_parser.as_ref(), audio_path, cue_track, tags) {
                 Ok(tags_content) => {
-                    let content_opt = tags_content.get(0).unwrap();
-                    let content = content_opt.as_ref().unwrap();
-                    if let Some(counting) = mut_map.get_mut(content) {
-                        let mut_counting = Rc::get_mut(counting).unwrap();
-                        *mut_counting += 1;
-                    } else {
-                        mut_map.insert(content.clone(), Rc::new(1));
-                    }
+                    let Some(content) = tags_content.first().and_then(Option::as_ref) else {
+                        return;
+                    };
+                    *mut_aggr.entry(AggregationKey(content.clone())).or_insert(0) += 1;
                 }
                 Err(error) => {
-                    // TODO aggregate audio_path in order to identify file in error.
                     // TODO try to fix lib if possible
-                    mut_errors.push(error);
+                    mut_errors.push((audio_path.to_path_buf(), error));
                 }
             };
         }
     };
-    visitor::visit_mut(path, &mut closure_sample_aggr).unwrap();
-    (sample_aggr, errors)
+    visitor::visit_tracks_mut(path, &mut closure_sample_aggr).unwrap();
+    let sample_aggr = sample_aggr
+        .into_iter()
+        .map(|(AggregationKey(value), count)| (value, count))
+        .collect();
+    (Rc::new(sample_aggr), errors)
+}
+
+/// Tag value ordered by MetadataValue::total_cmp, so that values of any type can be counted
+struct AggregationKey(MetadataValue);
+
+impl PartialEq for AggregationKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for AggregationKey {}
+
+impl PartialOrd for AggregationKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AggregationKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
 }
 
 /// Filters Audio files and returns music path.
 /// For now it only accepts the first tag
+/// CUE sheet tracks are filtered separately, their path is followed by the track number i.e "album.flac #03".
+/// Unreadable files are reported by path, the scan goes on.
 ///
 /// # Arguments
 /// * `path` - Audio folder to parse recursively
 /// * `metadata_parser` - Audio file reader
-/// * `tags` - filtering tags, default behavior is an "And" connector
-/// * `value` - tag value, for the first tag list only for now.
+/// * `tags` - filtering tags, default behavior is an "And" connector, nothing is filtered if empty
+/// * `value` - tag value, for the first tag list only for now. Parsed as the tag type, i.e
+///   "44100.0" matches a 44100 frame rate
 pub fn filter_by(
     path: &Path,
     metadata_parser: &Box<dyn MetadataParserWrapper>,
     tags: &Vec<AudioTag>,
     value: &str,
-) -> (
-    Rc<HashMap<String, Rc<Vec<String>>>>,
-    Rc<Vec<Box<dyn Error>>>,
-) {
+) -> (Rc<HashMap<String, Rc<Vec<String>>>>, Rc<PathErrors>) {
     let mut filtered: Rc<HashMap<String, Rc<Vec<String>>>> = Rc::new(HashMap::new());
-    let mut errors: Rc<Vec<Box<dyn Error>>> = Rc::new(Vec::new());
+    let mut errors: Rc<PathErrors> = Rc::new(Vec::new());
+    let Some(filter_tag) = tags.first() else {
+        return (filtered, errors);
+    };
 
     let mut closure_sample_filter = {
         let mut_map = Rc::get_mut(&mut filtered).unwrap();
         let mut_errors = Rc::get_mut(&mut errors).unwrap();
-        move |_dir: &DirEntry, audio_path: &Path| {
-            match metadata_parser.get_metadata_string(audio_path, &tags) {
+        let filter_value = MetadataValue::parse(filter_tag, value);
+        move |audio_path: &Path, cue_track: Option<&CueTrack>| {
+            match get_track_metadata(metadata_parser.as_ref(), audio_path, cue_track, tags) {
                 Ok(metadata) => {
-                    let metadata_content_opt = metadata.get(0).unwrap();
-                    let metadata_content = metadata_content_opt.as_ref().unwrap();
-                    if metadata_content.as_str() == value {
-                        if let Some(filtered_sound) = mut_map.get_mut(metadata_content) {
+                    let Some(metadata_content) = metadata.first().and_then(Option::as_ref) else {
+                        return;
+                    };
+                    if *metadata_content == filter_value {
+                        let metadata_content = metadata_content.to_string();
+                        let track_name = get_track_name(audio_path, cue_track);
+                        if let Some(filtered_sound) = mut_map.get_mut(&metadata_content) {
                             let mut_filtered_sound = Rc::get_mut(filtered_sound).unwrap();
-                            mut_filtered_sound.push(String::from(audio_path.to_str().unwrap()));
+                            mut_filtered_sound.push(track_name);
                         } else {
-                            mut_map.insert(
-                                metadata_content.clone(),
-                                Rc::new(vec![String::from(audio_path.to_str().unwrap())]),
-                            );
+                            mut_map.insert(metadata_content, Rc::new(vec![track_name]));
                         }
                     }
                 }
                 Ok(_) => (),
                 Err(error) => {
-                    // TODO aggregate audio_path in order to identify file in error.
                     // TODO try to fix lib if possible
-                    mut_errors.push(error);
+                    mut_errors.push((audio_path.to_path_buf(), error));
                 }
             };
         }
     };
-    visitor::visit_mut(path, &mut closure_sample_filter).unwrap();
+    visitor::visit_tracks_mut(path, &mut closure_sample_filter).unwrap();
     (filtered, errors)
 }
 
+/// Applies tag edits on audio files matching a tag value, every file if no filter is given.
+/// CUE sheet tracks share the tags of their audio file, which is matched and edited as a whole.
+/// Unreadable or unwritable files are reported by path, the scan goes on.
+///
+/// # Arguments
+/// * `path` - Audio folder to parse recursively
+/// * `metadata_parser` - Audio file reader & writer
+/// * `filter` - tag and value files must match, parsed as the tag type, every file is edited if None
+/// * `edits` - tag changes, applied in order
+/// * `dry_run` - lists files to edit without writing them, files which can't be edited being
+///   reported as errors
+///
+/// # Returns
+/// (sorted edited files, or files to edit on dry run, errors)
+pub fn edit_by(
+    path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    filter: Option<(AudioTag, &str)>,
+    edits: &[TagEdit],
+    dry_run: bool,
+) -> (Vec<PathBuf>, PathErrors) {
+    let mut edited = Vec::new();
+    let mut errors = Vec::new();
+    let filter = filter.map(|(tag, value)| (tag, MetadataValue::parse(&tag, value)));
+
+    let mut closure_edit = |_dir: &DirEntry, audio_path: &Path| {
+        if let Some((tag, filter_value)) = &filter {
+            match metadata_parser.get_metadata(audio_path, &vec![*tag]) {
+                Ok(metadata) => {
+                    if metadata.first().and_then(Option::as_ref) != Some(filter_value) {
+                        return;
+                    }
+                }
+                Err(error) => {
+                    errors.push((audio_path.to_path_buf(), error));
+                    return;
+                }
+            }
+        }
+        let written = if dry_run {
+            tag_writer::check_tags(audio_path, edits).map(|_| ())
+        } else {
+            metadata_parser.write_tags(audio_path, edits)
+        };
+        if let Err(error) = written {
+            errors.push((audio_path.to_path_buf(), error));
+            return;
+        }
+        edited.push(audio_path.to_path_buf());
+    };
+    if let Err(error) = visitor::visit_mut(path, &mut closure_edit) {
+        errors.push((path.to_path_buf(), error.into()));
+    }
+    edited.sort();
+    (edited, errors)
+}
+
+/// Groups audio files holding a same recording, whatever their format or bitrate, by comparing
+/// acoustic fingerprints of their beginning. Each file is compared to every other one, which may
+/// take several minutes on large libraries.
+/// Unreadable files are reported by path, the scan goes on.
+///
+/// # Arguments
+/// * `path` - Audio folder to parse recursively
+/// * `metadata_parser` - Audio file reader
+/// * `length` - audio fingerprinted at the start of each file
+/// * `min_similarity` - from 0 to 1, files at least this similar to a group first file join it
+///
+/// # Returns
+/// (groups of sorted duplicate files, errors)
+pub fn find_duplicates(
+    path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    length: Duration,
+    min_similarity: f64,
+) -> (Vec<Vec<PathBuf>>, PathErrors) {
+    let mut fingerprints = Vec::new();
+    let mut errors = Vec::new();
+
+    let mut closure_fingerprint = |_dir: &DirEntry, audio_path: &Path| match metadata_parser
+        .get_fingerprint(audio_path, length)
+    {
+        Ok(fingerprint) => fingerprints.push((audio_path.to_path_buf(), fingerprint)),
+        Err(error) => errors.push((audio_path.to_path_buf(), error)),
+    };
+    if let Err(error) = visitor::visit_mut(path, &mut closure_fingerprint) {
+        errors.push((path.to_path_buf(), error.into()));
+    }
+    fingerprints.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut duplicates = Vec::new();
+    let mut grouped = vec![false; fingerprints.len()];
+    for (idx, (audio_path, fingerprint)) in fingerprints.iter().enumerate() {
+        if grouped[idx] {
+            continue;
+        }
+        let mut group = vec![audio_path.clone()];
+        for (other_idx, (other_path, other)) in fingerprints.iter().enumerate().skip(idx + 1) {
+            let similar = fingerprint
+                .similarity(other)
+                .is_some_and(|similarity| similarity >= min_similarity);
+            if !grouped[other_idx] && similar {
+                grouped[other_idx] = true;
+                group.push(other_path.clone());
+            }
+        }
+        if group.len() > 1 {
+            duplicates.push(group);
+        }
+    }
+    (duplicates, errors)
+}
+
+/// Values given by the CUE sheet track are preferred, the audio file is read otherwise
+// Vec is required by MetadataParserWrapper::get_metadata
+#[allow(clippy::ptr_arg)]
+fn get_track_metadata(
+    metadata_parser: &dyn MetadataParserWrapper,
+    audio_path: &Path,
+    cue_track: Option<&CueTrack>,
+    tags: &Vec<AudioTag>,
+) -> TagsResult {
+    let cue_values: Vec<Option<MetadataValue>> = tags
+        .iter()
+        .map(|tag| cue_track.and_then(|cue_track| cue_track.get_metadata(tag)))
+        .collect();
+    if cue_values.iter().all(Option::is_some) {
+        return Ok(cue_values);
+    }
+    let file_values = metadata_parser.get_metadata(audio_path, tags)?;
+    Ok(cue_values
+        .into_iter()
+        .zip(file_values)
+        .map(|(cue_value, file_value)| cue_value.or(file_value))
+        .collect())
+}
+
+fn get_track_name(audio_path: &Path, cue_track: Option<&CueTrack>) -> String {
+    let path = audio_path.display().to_string();
+    match cue_track {
+        Some(cue_track) => format!("{} #{:0>2}", path, cue_track.number),
+        None => path,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use audio_player::{MetadataParserBuilder, TagsResult};
+    use audio_player::{
+        AllTagsResult, AudioError, AudioResult, Fingerprint, MetadataParserBuilder, ReportResult,
+        SampleLayout, SampleStreamResult, TagEdit, TagsResult, VisualsResult,
+    };
     use std::error::Error;
     use std::fs::{self, File};
     use std::io::Read;
@@ -117,6 +301,7 @@ mod tests {
     use std::path::PathBuf;
     use std::sync::mpsc::Sender;
     use std::thread::{self, JoinHandle};
+    use std::time::Duration;
     use tempfile::Builder;
     use tempfile::NamedTempFile;
     use tempfile::TempPath;
@@ -124,31 +309,89 @@ mod tests {
     struct MetadataParserMock {}
 
     impl MetadataParserWrapper for MetadataParserMock {
-        fn get_metadata_string(
-            &self,
-            audio_path: &Path,
-            _target_metadata: &Vec<AudioTag>,
-        ) -> TagsResult {
+        fn get_metadata(&self, audio_path: &Path, _target_metadata: &Vec<AudioTag>) -> TagsResult {
             let mut buffer: String = String::new();
             println!("reading test file {:?}", audio_path);
             let mut audio = File::open(audio_path).unwrap();
             audio.read_to_string(&mut buffer).unwrap();
-            Ok(vec![Some(buffer)])
+            if buffer == "corrupted" {
+                return Err(AudioError::Decode("mocked corrupted file".to_string()));
+            }
+            Ok(vec![Some(MetadataValue::Text(buffer))])
+        }
+        fn get_raw_metadata(&self, _audio_path: &Path, _keys: &[String]) -> TagsResult {
+            Ok(Vec::new())
+        }
+        fn get_all_tags(&self, _audio_path: &Path) -> AllTagsResult {
+            Ok(Vec::new())
         }
 
-        fn print_metadata(&self, _audio_path: &Path) {}
+        fn print_metadata(&self, _audio_path: &Path) -> AudioResult<()> {
+            Ok(())
+        }
+        fn get_file_report(&self, _audio_path: &Path) -> ReportResult {
+            Err(AudioError::UnsupportedFormat(
+                "no report for mocked files".to_string(),
+            ))
+        }
         fn print_tags(&self, _audio_path: &Path) {}
-        fn print_visuals(&self, _audio_path: &Path) {}
-        fn get_file_samples(&self, _audio_path: &Path) -> Option<Box<Vec<f32>>> {
-            None
+        fn print_visuals(&self, _audio_path: &Path) -> AudioResult<()> {
+            Ok(())
+        }
+        fn extract_visuals(&self, _audio_path: &Path, _out_dir: &Path) -> VisualsResult {
+            Ok(Vec::new())
+        }
+        /// Mocked files content is their single tag value
+        fn write_tags(&self, audio_path: &Path, edits: &[TagEdit]) -> AudioResult<()> {
+            for edit in edits {
+                match edit {
+                    TagEdit::Set(_, value) => fs::write(audio_path, value)?,
+                    TagEdit::Remove(_) => fs::write(audio_path, "")?,
+                }
+            }
+            Ok(())
+        }
+        fn get_sample_stream(
+            &self,
+            _audio_path: &Path,
+            _layout: SampleLayout,
+        ) -> SampleStreamResult {
+            Ok(Box::new(std::iter::empty()))
+        }
+        fn get_file_samples(&self, _audio_path: &Path) -> AudioResult<Box<Vec<f32>>> {
+            Err(AudioError::Decode(
+                "no samples for mocked files".to_string(),
+            ))
+        }
+        fn get_file_range_samples(
+            &self,
+            audio_path: &Path,
+            _from: Option<Duration>,
+            _to: Option<Duration>,
+        ) -> AudioResult<Vec<f32>> {
+            self.get_file_samples(audio_path).map(|samples| *samples)
+        }
+        /// Mocked files bytes are repeated into fingerprint values
+        fn get_fingerprint(&self, audio_path: &Path, length: Duration) -> AudioResult<Fingerprint> {
+            let content = fs::read_to_string(audio_path)?;
+            if content == "corrupted" {
+                return Err(AudioError::Decode("mocked corrupted file".to_string()));
+            }
+            Ok(Fingerprint {
+                duration: length,
+                values: content
+                    .bytes()
+                    .map(|byte| u32::from(byte) * 0x0101_0101)
+                    .collect(),
+            })
         }
         fn get_live_sample(
             &self,
             _audio_path: &Path,
             _live_sample: Sender<(usize, usize, Vec<f32>)>,
             _live_sample_written: &mut Rc<usize>,
-        ) -> JoinHandle<()> {
-            thread::spawn(|| {})
+        ) -> JoinHandle<AudioResult<()>> {
+            thread::spawn(|| Ok(()))
         }
     }
 
@@ -251,6 +494,118 @@ mod tests {
         drop_temp_dir(root_dir);
     }
 
+    #[test]
+    fn it_collects_errors_by_path_with_mock() {
+        let tag = vec![AudioTag::from("who cares")];
+        let root_dir = Builder::new().tempdir_in("./").unwrap();
+        let root_path = root_dir.into_path();
+        let (root_audio, root_dir) = create_temp_file(&root_path, false, "Metal");
+        let (corrupted_audio, _) = create_temp_file(&root_path, false, "corrupted");
+        let metadata_parser = build_metadata_parser_mock();
+
+        let (result_aggr, errors) = aggregate_by(&root_path, &metadata_parser, &tag);
+        assert_eq!(result_aggr.len(), 1);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, corrupted_audio.to_path_buf());
+
+        drop(root_audio);
+        drop(corrupted_audio);
+        drop_temp_dir(root_dir);
+    }
+
+    #[test]
+    fn it_aggregate_cue_tracks_with_mock() {
+        let tag = vec![AudioTag::from("who cares")];
+        let root_dir = Builder::new().tempdir_in("./").unwrap();
+        let root_path = root_dir.into_path();
+        let (album_audio, root_dir) = create_temp_file(&root_path, false, "Metal");
+        let cue_path = root_dir.join("album.cue");
+        let file_name = album_audio.file_name().unwrap().to_str().unwrap();
+        let cue_content = format!(
+            "FILE \"{}\" WAVE\nTRACK 01 AUDIO\nTITLE \"Intro\"\nINDEX 01 00:00:00\nTRACK 02 AUDIO\nTITLE \"Outro\"\nINDEX 01 03:10:00\n",
+            file_name
+        );
+        fs::write(&cue_path, cue_content).unwrap();
+        let metadata_parser = build_metadata_parser_mock();
+
+        // file content for tags the sheet doesn't give, counted once per cue track
+        let (result_aggr, _) = aggregate_by(&root_path, &metadata_parser, &tag);
+        assert_eq!(
+            *result_aggr,
+            vec![(MetadataValue::Text("Metal".to_string()), 2)]
+        );
+        let (result_aggr, _) =
+            aggregate_by(&root_path, &metadata_parser, &vec![AudioTag::TrackName]);
+        assert_eq!(result_aggr.len(), 2);
+
+        drop(album_audio);
+        fs::remove_file(cue_path).unwrap();
+        drop_temp_dir(root_dir);
+    }
+
+    #[test]
+    fn it_edits_filtered_files_with_mock() {
+        let tag = AudioTag::Genre;
+        let root_dir = Builder::new().tempdir_in("./").unwrap();
+        let root_path = root_dir.into_path();
+        let (metal_audio, root_dir) = create_temp_file(&root_path, false, "Metal");
+        let (rock_audio, _) = create_temp_file(&root_path, false, "Rock");
+        let metadata_parser = build_metadata_parser_mock();
+        let edits = [TagEdit::Set(tag, "Jazz".to_string())];
+
+        let (edited, errors) = edit_by(
+            &root_path,
+            metadata_parser.as_ref(),
+            Some((tag, "Metal")),
+            &edits,
+            true,
+        );
+        assert_eq!(edited, vec![metal_audio.to_path_buf()]);
+        assert!(errors.is_empty());
+        assert_eq!(fs::read_to_string(&metal_audio).unwrap(), "Metal");
+
+        let (edited, _) = edit_by(
+            &root_path,
+            metadata_parser.as_ref(),
+            Some((tag, "Metal")),
+            &edits,
+            false,
+        );
+        assert_eq!(edited.len(), 1);
+        let (result_aggr, _) = aggregate_by(&root_path, &metadata_parser, &vec![tag]);
+        assert_eq!(
+            *result_aggr,
+            vec![
+                (MetadataValue::Text("Jazz".to_string()), 1),
+                (MetadataValue::Text("Rock".to_string()), 1)
+            ]
+        );
+
+        drop(metal_audio);
+        drop(rock_audio);
+        drop_temp_dir(root_dir);
+    }
+
+    #[test]
+    fn it_reports_unwritable_formats_on_dry_run() {
+        let root_dir = Builder::new().tempdir_in("./").unwrap();
+        let root_path = root_dir.into_path();
+        let (mp3_audio, root_dir) = create_temp_file(&root_path, false, "Metal");
+        let ogg_audio = root_dir.join("vorbis.ogg");
+        fs::write(&ogg_audio, "Metal").unwrap();
+        let metadata_parser = build_metadata_parser_mock();
+        let edits = [TagEdit::Remove(AudioTag::Comment)];
+
+        let (edited, errors) = edit_by(&root_path, metadata_parser.as_ref(), None, &edits, true);
+        assert_eq!(edited, vec![mp3_audio.to_path_buf()]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, ogg_audio);
+
+        drop(mp3_audio);
+        fs::remove_file(ogg_audio).unwrap();
+        drop_temp_dir(root_dir);
+    }
+
     #[test]
     fn it_filters_with_mock() {
         let metal_content = "Metal";
@@ -270,6 +625,8 @@ mod tests {
         let (result_aggr_genre, _) = filter_by(&root_path, &metadata_parser, &tag, metal_content);
         assert_eq!(result_aggr_genre.len(), 1);
         assert_eq!(result_aggr_genre.get(metal_content).unwrap().len(), 2);
+        let (no_tag_filtered, _) = filter_by(&root_path, &metadata_parser, &vec![], metal_content);
+        assert!(no_tag_filtered.is_empty());
 
         // FIXME for some reason I need to force drop audio file before removing dir in this test case. but not in it_with_temp_files
         // investigate this behavior. (not related to aggregate_by_sample_rate)
@@ -281,4 +638,34 @@ mod tests {
         drop_temp_dir(sub_dir2);
         drop_temp_dir(root_dir);
     }
+
+    #[test]
+    fn it_finds_duplicates_with_mock() {
+        let root_dir = Builder::new().tempdir_in("./").unwrap();
+        let root_path = root_dir.into_path();
+        let (original, root_dir) = create_temp_file(&root_path, false, "aaaaaaaa");
+        let (reencoded, _) = create_temp_file(&root_path, false, "aaaaaaab");
+        // 6 bits out of 8 differ from "a"
+        let (other, _) = create_temp_file(&root_path, false, "^^^^^^^^");
+        let (corrupted, _) = create_temp_file(&root_path, false, "corrupted");
+        let metadata_parser = build_metadata_parser_mock();
+
+        let (duplicates, errors) = find_duplicates(
+            &root_path,
+            metadata_parser.as_ref(),
+            Duration::from_secs(120),
+            0.9,
+        );
+        let mut expected = vec![original.to_path_buf(), reencoded.to_path_buf()];
+        expected.sort();
+        assert_eq!(duplicates, vec![expected]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, corrupted.to_path_buf());
+
+        drop(original);
+        drop(reencoded);
+        drop(other);
+        drop(corrupted);
+        drop_temp_dir(root_dir);
+    }
 }
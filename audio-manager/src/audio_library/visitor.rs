@@ -1,32 +1,24 @@
+use audio_player::{audio_format, cue_sheet, AudioFormat, CueTrack, FormatDetection};
+use log::warn;
 use std::{
+    collections::{BTreeMap, HashMap},
     fs::{self, DirEntry},
     io,
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-/// Symphonia can handle more types but I'm just lazy to write them in this enum
-enum AudioFormat {
-    MP3,
-    FLAC,
-}
-
-fn match_audio_type(extension: &str) -> Option<AudioFormat> {
-    match extension {
-        "mp3" => Some(AudioFormat::MP3),
-        "flac" => Some(AudioFormat::FLAC),
-        _ => None,
-    }
-}
-
+/// Audio files are recognized by their content, by their extension if the content isn't.
+/// Only formats audio-player is built to decode are accepted, see its cargo features
 fn check_audio_format(path: &Path) -> bool {
-    if let Some(ext) = path.extension() {
-        if let Some(ext_str) = ext.to_str() {
-            if let Some(_) = match_audio_type(ext_str) {
-                return true;
-            }
-        }
-    }
-    false
+    let format = match audio_format::detect_format(path) {
+        Ok(detection) => detection.format(),
+        // Unreadable files are still visited by extension, so that their error gets reported.
+        Err(_) => path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(AudioFormat::from_extension),
+    };
+    format.is_some_and(|format| format.is_supported())
 }
 
 /// Visitor for valid audio files
@@ -66,3 +58,72 @@ where
     }
     Ok(())
 }
+
+/// Mutable state visitor for audio tracks.
+/// A file described by a CUE sheet is visited once per sheet track, other files once with None.
+/// Sheets of a folder are read once, when visiting its first audio file
+pub fn visit_tracks_mut<T>(dir: &Path, cb: &mut T) -> io::Result<()>
+where
+    T: FnMut(&Path, Option<&CueTrack>),
+{
+    let mut sheets_by_dir: HashMap<PathBuf, Vec<Vec<CueTrack>>> = HashMap::new();
+    let mut closure_tracks = |_dir: &DirEntry, audio_path: &Path| {
+        let folder = cue_sheet::folder_of(audio_path);
+        let sheets = sheets_by_dir
+            .entry(folder.to_path_buf())
+            .or_insert_with(|| {
+                // An unreadable folder shouldn't hide its files, they're visited as single tracks.
+                cue_sheet::read_folder(folder).unwrap_or_else(|err| {
+                    warn!("cue sheets error for {:?}: {}", folder, err);
+                    Vec::new()
+                })
+            });
+        let cue_tracks = cue_sheet::find_tracks(sheets, audio_path);
+        if cue_tracks.is_empty() {
+            cb(audio_path, None);
+        }
+        for cue_track in &cue_tracks {
+            cb(audio_path, Some(cue_track));
+        }
+    };
+    visit_mut(dir, &mut closure_tracks)
+}
+
+/// Audio files whose extension names another format than their content one
+///
+/// # Returns
+/// sorted mismatching files with their detected formats
+pub fn find_format_mismatches(path: &Path) -> io::Result<Vec<(PathBuf, FormatDetection)>> {
+    let mut mismatches = Vec::new();
+    let mut closure_mismatch = |_dir: &DirEntry, audio_path: &Path| {
+        if let Ok(detection) = audio_format::detect_format(audio_path) {
+            if detection.is_mismatch() {
+                mismatches.push((audio_path.to_path_buf(), detection));
+            }
+        }
+    };
+    visit_mut(path, &mut closure_mismatch)?;
+    mismatches.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(mismatches)
+}
+
+/// Groups valid audio files by parent folder, each folder being an album
+///
+/// # Returns
+/// sorted audio files by album folder
+pub fn group_by_album(path: &Path) -> io::Result<BTreeMap<PathBuf, Vec<PathBuf>>> {
+    let mut albums: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+    let mut closure_album_group = |_dir: &DirEntry, audio_path: &Path| {
+        if let Some(album_path) = audio_path.parent() {
+            albums
+                .entry(album_path.to_path_buf())
+                .or_default()
+                .push(audio_path.to_path_buf());
+        }
+    };
+    visit_mut(path, &mut closure_album_group)?;
+    for tracks in albums.values_mut() {
+        tracks.sort();
+    }
+    Ok(albums)
+}
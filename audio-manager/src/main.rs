@@ -1,17 +1,22 @@
-use audio_manager::audio_library::{metadata_aggregator, visitor};
-use audio_player::{AudioTag, MetadataParserBuilder, MetadataParserWrapper};
-use std::fs::DirEntry;
+use audio_manager::audio_library::{
+    cover_extractor, loudness_scan, metadata_aggregator, visitor, PathErrors,
+};
+use audio_player::{
+    AudioTag, CueTrack, Loudness, MetadataParserBuilder, MetadataParserWrapper, TagEdit,
+};
 use std::path::Path;
 use std::rc::Rc;
+use std::time::Duration;
 mod args;
 use args::Cli;
-use clap::Parser;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
 use log::error;
 
 fn main() {
-    let args = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let args = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
 
-    let code = match run(&args) {
+    let code = match run(&args, &matches) {
         Ok(code) => code,
         Err(err) => {
             error!("{}", err.to_string().to_lowercase());
@@ -21,7 +26,7 @@ fn main() {
     std::process::exit(code)
 }
 
-fn run(args: &Cli) -> Result<i32, Box<dyn std::error::Error>> {
+fn run(args: &Cli, matches: &ArgMatches) -> Result<i32, Box<dyn std::error::Error>> {
     let music_folder_path = Path::new(&args.input);
     let metadata_parser = MetadataParserBuilder::build();
 
@@ -35,7 +40,21 @@ fn run(args: &Cli) -> Result<i32, Box<dyn std::error::Error>> {
             &vec![AudioTag::from(tag_agg)],
         );
     }
-    if let (Some(tag_filter), Some(tag_value)) =
+    let filter = args
+        .filter_tag
+        .as_deref()
+        .zip(args.filter_value.as_deref())
+        .map(|(tag_filter, tag_value)| (AudioTag::from(tag_filter), tag_value));
+    let edits = get_edits(args, matches);
+    if !edits.is_empty() {
+        process_edit(
+            music_folder_path,
+            metadata_parser.as_ref(),
+            filter,
+            &edits,
+            args.dry_run,
+        );
+    } else if let (Some(tag_filter), Some(tag_value)) =
         (args.filter_tag.as_deref(), args.filter_value.as_deref())
     {
         process_filter(
@@ -45,9 +64,37 @@ fn run(args: &Cli) -> Result<i32, Box<dyn std::error::Error>> {
             tag_value,
         );
     }
+    if args.loudness_scan {
+        process_loudness_scan(music_folder_path);
+    }
+    if let Some(out_dir) = args.extract_covers.as_deref() {
+        process_extract_covers(music_folder_path, metadata_parser.as_ref(), out_dir);
+    }
+    if args.check_formats {
+        process_check_formats(music_folder_path)?;
+    }
+    if args.find_duplicates {
+        process_find_duplicates(
+            music_folder_path,
+            metadata_parser.as_ref(),
+            args.fingerprint_length,
+            args.min_similarity,
+        );
+    }
     Ok(1)
 }
 
+/// Tag edits in command line order, --set-tag & --remove-tag being interleaved
+fn get_edits(args: &Cli, matches: &ArgMatches) -> Vec<TagEdit> {
+    let indices = |id| matches.indices_of(id).into_iter().flatten();
+    let mut edits: Vec<(usize, &TagEdit)> = indices("set_tag")
+        .zip(&args.set_tag)
+        .chain(indices("remove_tag").zip(&args.remove_tag))
+        .collect();
+    edits.sort_by_key(|(index, _)| *index);
+    edits.into_iter().map(|(_, edit)| edit.clone()).collect()
+}
+
 fn process_aggregation(
     music_folder_path: &Path,
     metadata_parser: &Box<dyn MetadataParserWrapper>,
@@ -55,8 +102,11 @@ fn process_aggregation(
 ) {
     let (res_metadata_aggr, errs) =
         metadata_aggregator::aggregate_by(music_folder_path, &metadata_parser, tag_agg);
-    println!("metadatas aggregated {:?}", res_metadata_aggr);
-    println!("errors {:?}", errs);
+    println!("metadatas aggregated");
+    for (value, count) in res_metadata_aggr.iter() {
+        println!("    {} : {}", value, count);
+    }
+    print_errors(&errs);
 }
 fn process_filter(
     music_folder_path: &Path,
@@ -68,7 +118,101 @@ fn process_filter(
         metadata_aggregator::filter_by(music_folder_path, &metadata_parser, tag_filter, tag_value);
     // TODO add an iterator layer logic for haevy results.
     println!("metadatas filtered {:?}", res_metadata_filter);
-    println!("errors {:?}", errs);
+    print_errors(&errs);
+}
+
+fn process_edit(
+    music_folder_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    filter: Option<(AudioTag, &str)>,
+    edits: &[TagEdit],
+    dry_run: bool,
+) {
+    let (edited, errs) =
+        metadata_aggregator::edit_by(music_folder_path, metadata_parser, filter, edits, dry_run);
+    if dry_run {
+        println!("files to edit {} (dry run)", edited.len());
+    } else {
+        println!("files edited {}", edited.len());
+    }
+    for path in edited {
+        println!("    {:?}", path);
+    }
+    print_errors(&errs);
+}
+
+fn process_loudness_scan(music_folder_path: &Path) {
+    let (albums, errs) = loudness_scan::scan_albums(music_folder_path);
+    for (album_path, album) in albums {
+        println!("album {:?} : {}", album_path, fmt_loudness(&album.album));
+        for (track_path, track) in album.tracks {
+            println!("    track {:?} : {}", track_path, fmt_loudness(&track));
+        }
+    }
+    print_errors(&errs);
+}
+
+fn process_extract_covers(
+    music_folder_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    out_dir: &Path,
+) {
+    let (covers, errs) =
+        cover_extractor::extract_album_covers(music_folder_path, metadata_parser, out_dir);
+    for (album_path, written) in covers {
+        println!("album {:?} : {:?}", album_path, written);
+    }
+    print_errors(&errs);
+}
+
+fn process_check_formats(music_folder_path: &Path) -> std::io::Result<()> {
+    let mismatches = visitor::find_format_mismatches(music_folder_path)?;
+    println!("format mismatches {}", mismatches.len());
+    for (path, detection) in mismatches {
+        if let (Some(extension), Some(content)) = (detection.extension, detection.content) {
+            println!(
+                "    {:?} : {} content, {} extension",
+                path, content, extension
+            );
+        }
+    }
+    Ok(())
+}
+
+fn process_find_duplicates(
+    music_folder_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    length: Duration,
+    min_similarity: f64,
+) {
+    let (duplicates, errs) = metadata_aggregator::find_duplicates(
+        music_folder_path,
+        metadata_parser,
+        length,
+        min_similarity,
+    );
+    println!("duplicate groups {}", duplicates.len());
+    for group in duplicates {
+        println!("    {:?}", group);
+    }
+    print_errors(&errs);
+}
+
+fn print_errors(errors: &PathErrors) {
+    println!("errors {}", errors.len());
+    for (path, error) in errors {
+        println!("    {:?} : {}", path, error);
+    }
+}
+
+fn fmt_loudness(loudness: &Loudness) -> String {
+    format!(
+        "{:.2} LUFS, range {:.2} LU, true peak {:.6}, replaygain {:.2} dB",
+        loudness.integrated,
+        loudness.range,
+        loudness.true_peak,
+        loudness.replay_gain_db()
+    )
 }
 
 pub fn count_music(path: &Path) {
@@ -76,10 +220,55 @@ pub fn count_music(path: &Path) {
 
     let mut closure_counter = {
         let test_mut = Rc::get_mut(&mut counter).unwrap();
-        move |_dir: &DirEntry, _audio_path: &Path| {
+        move |_audio_path: &Path, _cue_track: Option<&CueTrack>| {
             *test_mut += 1;
         }
     };
-    visitor::visit_mut(path, &mut closure_counter).unwrap();
+    visitor::visit_tracks_mut(path, &mut closure_counter).unwrap();
     println!("total {:?}", counter.as_ref());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_edits_order() {
+        let matches = Cli::command().get_matches_from([
+            "audio-manager",
+            "-i",
+            "/lib",
+            "--all",
+            "--remove-tag",
+            "genre",
+            "--set-tag",
+            "genre=Jazz",
+            "--remove-tag",
+            "comment",
+        ]);
+        let args = Cli::from_arg_matches(&matches).unwrap();
+        assert_eq!(
+            get_edits(&args, &matches),
+            vec![
+                TagEdit::Remove(AudioTag::Genre),
+                TagEdit::Set(AudioTag::Genre, "Jazz".to_string()),
+                TagEdit::Remove(AudioTag::Comment),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_unfiltered_edits() {
+        let edit = ["audio-manager", "-i", "/lib", "--set-tag", "genre=Jazz"];
+        assert!(Cli::command().try_get_matches_from(edit).is_err());
+        assert!(Cli::command()
+            .try_get_matches_from(edit.iter().chain(&["--all"]))
+            .is_ok());
+        assert!(Cli::command()
+            .try_get_matches_from(
+                edit.iter()
+                    .chain(&["-f", "genre", "--filter-value", "Rock"])
+            )
+            .is_ok());
+    }
+}
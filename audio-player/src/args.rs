@@ -1,14 +1,21 @@
 use std::path::PathBuf;
+use std::time::Duration;
 
+use audio_player::time_parser::parse_time;
+use audio_player::{CrossfadeCurve, ReplayGainMode, ResampleQuality, WavBitDepth};
 use clap::ArgAction::Append;
-use clap::Parser;
+use clap::{ArgGroup, Parser, ValueEnum};
+
+/// Longest --crossfade, in seconds
+const MAX_CROSSFADE_SECONDS: f64 = 12.0;
 
 #[derive(Parser)]
 #[command(name="J.Garnier", author, version, about, long_about = None)]
+#[command(group(ArgGroup::new("decode_action").args(["full_audio_sample", "export_wav"]).multiple(true)))]
 pub struct Cli {
-    /// Audio path in absolute path
-    #[arg(short, long, required = true)]
-    pub input: PathBuf,
+    /// Audio path in absolute path, repeat it to play several files in a row
+    #[arg(short, long, required = true, action=Append)]
+    pub input: Vec<PathBuf>,
     /// Play audio
     #[arg(short, long)]
     pub play: bool,
@@ -18,10 +25,122 @@ pub struct Cli {
     /// Get audio specific tag or metadata i.e ["duration","frameRate","channels","genre"]
     #[arg(short, long, action=Append, conflicts_with_all=&["analyze"])]
     pub tag: Option<Vec<String>>,
+    /// Output format of --analyze and --tag
+    #[arg(long, value_enum, default_value_t = ReportFormat::Text)]
+    pub format: ReportFormat,
+    /// Get audio tag by its container key, case insensitive i.e ["TXXX:CATALOGNUMBER", "ORIGINALYEAR"]
+    #[arg(long, action=Append)]
+    pub raw_tag: Option<Vec<String>>,
+    /// Get every audio tag as key / value pairs
+    #[arg(long)]
+    pub all_tags: bool,
+    /// Write embedded pictures into given folder, named by usage i.e "front-cover.jpg",
+    /// into an "<input name>" sub folder per input when several are given
+    #[arg(long)]
+    pub extract_cover: Option<PathBuf>,
     /// Get full audio data
     #[arg(long)]
     pub full_audio_sample: bool,
+    /// Decode audio into a WAV file, or into "<input name>.wav" files of a folder, required with several inputs
+    #[arg(long)]
+    pub export_wav: Option<PathBuf>,
+    /// Sample format of --export-wav
+    #[arg(long, value_enum, default_value_t = WavBitDepth::Int16, requires = "export_wav")]
+    pub bit_depth: WavBitDepth,
+    /// Sample rate of --export-wav, audio one if not set, resampled with --resample-quality
+    #[arg(long, requires = "export_wav", value_parser = clap::value_parser!(u32).range(1..))]
+    pub sample_rate: Option<u32>,
+    /// Start time of --full-audio-sample & --export-wav, as seconds or mm:ss i.e ["95", "1:35", "1:35.5"]
+    #[arg(long, requires = "decode_action", value_parser = parse_time)]
+    pub from: Option<Duration>,
+    /// End time of --full-audio-sample & --export-wav, as seconds or mm:ss i.e ["95", "1:35", "1:35.5"]
+    #[arg(long, requires = "decode_action", value_parser = parse_time)]
+    pub to: Option<Duration>,
+    /// Get audio acoustic fingerprint, Chromaprint encoded, and similarity of next inputs to the first one
+    #[arg(long)]
+    pub fingerprint: bool,
+    /// Audio fingerprinted by --fingerprint from the start of file, as seconds or mm:ss i.e ["30", "2:00"]
+    #[arg(long, default_value = "120", requires = "fingerprint", value_parser = parse_time)]
+    pub fingerprint_length: Duration,
     /// Get live audio data stream
     #[arg(long, conflicts_with_all=&["play"])]
     pub live_audio_sample: bool,
+    /// Resampling quality when output device rate differs from audio one
+    #[arg(long, value_enum, default_value_t = ResampleQuality::Balanced)]
+    pub resample_quality: ResampleQuality,
+    /// Software volume in dB, boosted samples are soft limited i.e ["-6", "3.5"]
+    #[arg(long, default_value_t = 0.0, allow_negative_numbers = true)]
+    pub volume: f32,
+    /// Start playing muted
+    #[arg(long)]
+    pub mute: bool,
+    /// ReplayGain tags to apply, with clipping prevention
+    #[arg(long, value_enum, default_value_t = ReplayGainMode::Off)]
+    pub replaygain: ReplayGainMode,
+    /// Crossfade between queued tracks in seconds, from 0 to 12. Tracks of a same album are not crossfaded
+    #[arg(long, value_parser = parse_crossfade, default_value = "0")]
+    pub crossfade: Duration,
+    /// Crossfade volume curve
+    #[arg(long, value_enum, default_value_t = CrossfadeCurve::Linear)]
+    pub crossfade_curve: CrossfadeCurve,
+    /// Start playing at given time, as seconds or mm:ss i.e ["95", "1:35", "1:35.5"]
+    #[arg(long, value_parser = parse_time)]
+    pub seek: Option<Duration>,
+    /// Where audio is played
+    #[arg(long, value_enum, default_value_t = OutputBackendKind::Device)]
+    pub output_backend: OutputBackendKind,
+    /// WAV file written by the "wav" output backend
+    #[arg(long, required_if_eq("output_backend", "wav"))]
+    pub output_file: Option<PathBuf>,
+    /// Track index to play in a multi-track file, first supported track by default
+    #[arg(long)]
+    pub track: Option<usize>,
+    /// CUE sheet track number to play, from the sheet next to the first input file
+    #[arg(long, requires = "play", conflicts_with_all=&["seek"])]
+    pub cue_track: Option<u32>,
+}
+
+/// Output format selected by --format
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ReportFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per input file
+    Json,
+}
+
+/// Audio output selected by --output-backend
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputBackendKind {
+    /// Platform audio device
+    Device,
+    /// WAV file given by --output-file
+    Wav,
+    /// Audio is discarded
+    Null,
+}
+
+/// Parses a crossfade length, in seconds
+fn parse_crossfade(value: &str) -> Result<Duration, String> {
+    match value.parse::<f64>() {
+        Ok(seconds) if (0.0..=MAX_CROSSFADE_SECONDS).contains(&seconds) => {
+            Ok(Duration::from_secs_f64(seconds))
+        }
+        _ => Err(format!(
+            "invalid crossfade '{}', expected seconds from 0 to {}",
+            value, MAX_CROSSFADE_SECONDS
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_bounds_crossfade() {
+        assert_eq!(parse_crossfade("4.5"), Ok(Duration::from_millis(4500)));
+        assert!(parse_crossfade("12.5").is_err());
+        assert!(parse_crossfade("-1").is_err());
+    }
 }
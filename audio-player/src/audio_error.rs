@@ -0,0 +1,91 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use symphonia::core::errors::Error as SymphoniaError;
+
+pub type AudioResult<T> = Result<T, AudioError>;
+
+/// Errors of the audio-player API, a file failing doesn't abort the caller
+#[derive(Debug)]
+pub enum AudioError {
+    /// File extension is missing or not UTF-8, format can't be guessed
+    NoExtension,
+    /// No reader handles this container format, or one of its features
+    UnsupportedFormat(String),
+    /// No decoder handles this track codec, or one of its features
+    UnsupportedCodec(String),
+    Io(io::Error),
+    /// Malformed audio data, or content that can't be read once decoded
+    Decode(String),
+    /// Tag that can't be written, i.e computed from the audio stream, or an invalid value
+    UnwritableTag(String),
+    /// Audio output that can't be opened or written, or a playing thread stopped abruptly
+    Output(String),
+}
+
+impl fmt::Display for AudioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioError::NoExtension => f.write_str("no valid file extension"),
+            AudioError::UnsupportedFormat(msg) => write!(f, "unsupported format: {}", msg),
+            AudioError::UnsupportedCodec(msg) => write!(f, "unsupported codec: {}", msg),
+            AudioError::Io(err) => write!(f, "io error: {}", err),
+            AudioError::Decode(msg) => write!(f, "decode error: {}", msg),
+            AudioError::UnwritableTag(msg) => write!(f, "unwritable tag: {}", msg),
+            AudioError::Output(msg) => write!(f, "audio output error: {}", msg),
+        }
+    }
+}
+
+impl Error for AudioError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AudioError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for AudioError {
+    fn from(err: io::Error) -> AudioError {
+        AudioError::Io(err)
+    }
+}
+
+/// Unsupported errors are reported as format ones, decoders are built with
+/// commons::make_decoder to report them as codec ones
+impl From<SymphoniaError> for AudioError {
+    fn from(err: SymphoniaError) -> AudioError {
+        match err {
+            SymphoniaError::IoError(err) => AudioError::Io(err),
+            SymphoniaError::Unsupported(msg) => AudioError::UnsupportedFormat(msg.to_string()),
+            err => AudioError::Decode(err.to_string()),
+        }
+    }
+}
+
+impl From<id3::Error> for AudioError {
+    fn from(err: id3::Error) -> AudioError {
+        let msg = err.to_string();
+        match err.kind {
+            id3::ErrorKind::Io(err) => AudioError::Io(err),
+            _ => AudioError::Decode(format!("id3 tag: {}", msg)),
+        }
+    }
+}
+
+impl From<hound::Error> for AudioError {
+    fn from(err: hound::Error) -> AudioError {
+        match err {
+            hound::Error::IoError(err) => AudioError::Io(err),
+            err => AudioError::UnsupportedFormat(format!("wav file: {}", err)),
+        }
+    }
+}
+
+impl From<ebur128::Error> for AudioError {
+    fn from(err: ebur128::Error) -> AudioError {
+        AudioError::Decode(format!("loudness measure: {}", err))
+    }
+}
@@ -0,0 +1,259 @@
+//!
+//! Audio container detection from the first bytes of a file, for files without extension
+//! or with an extension naming another format.
+//!
+
+use serde::Serialize;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::AudioResult;
+
+/// Bytes needed to recognize any format
+const HEADER_LEN: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// MPEG audio, layer I to III
+    Mp3,
+    Flac,
+    Ogg,
+    Wav,
+    Aiff,
+    /// ISO base media file, i.e m4a
+    Mp4,
+    /// Matroska & WebM, i.e mka
+    Mkv,
+}
+
+impl AudioFormat {
+    pub const ALL: [AudioFormat; 7] = [
+        AudioFormat::Mp3,
+        AudioFormat::Flac,
+        AudioFormat::Ogg,
+        AudioFormat::Wav,
+        AudioFormat::Aiff,
+        AudioFormat::Mp4,
+        AudioFormat::Mkv,
+    ];
+
+    /// Formats decoded by this build, see audio-player cargo features
+    pub fn supported() -> Vec<AudioFormat> {
+        AudioFormat::ALL
+            .into_iter()
+            .filter(AudioFormat::is_supported)
+            .collect()
+    }
+
+    /// True if the cargo features this crate is built with decode this format
+    pub fn is_supported(&self) -> bool {
+        match self {
+            AudioFormat::Mp3 => true,
+            AudioFormat::Flac => cfg!(feature = "flac"),
+            AudioFormat::Ogg => cfg!(feature = "vorbis"),
+            AudioFormat::Wav => cfg!(feature = "wav"),
+            AudioFormat::Aiff => cfg!(feature = "aiff"),
+            // Container only, its tracks are either AAC or ALAC ones
+            AudioFormat::Mp4 => cfg!(all(
+                feature = "isomp4",
+                any(feature = "aac", feature = "alac")
+            )),
+            // Container only, its tracks are decoded by the other codec features
+            AudioFormat::Mkv => cfg!(feature = "mkv"),
+        }
+    }
+
+    /// Case insensitive, i.e "FLAC" or "m4a"
+    pub fn from_extension(extension: &str) -> Option<AudioFormat> {
+        match extension.to_ascii_lowercase().as_str() {
+            "mp3" | "mp2" | "mp1" => Some(AudioFormat::Mp3),
+            "flac" => Some(AudioFormat::Flac),
+            "ogg" | "oga" | "opus" => Some(AudioFormat::Ogg),
+            "wav" | "wave" => Some(AudioFormat::Wav),
+            "aiff" | "aif" | "aifc" => Some(AudioFormat::Aiff),
+            "mp4" | "m4a" | "m4b" => Some(AudioFormat::Mp4),
+            "mka" | "mkv" | "webm" => Some(AudioFormat::Mkv),
+            _ => None,
+        }
+    }
+
+    /// Extension symphonia expects for this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Mp3 => "mp3",
+            AudioFormat::Flac => "flac",
+            AudioFormat::Ogg => "ogg",
+            AudioFormat::Wav => "wav",
+            AudioFormat::Aiff => "aiff",
+            AudioFormat::Mp4 => "mp4",
+            AudioFormat::Mkv => "mkv",
+        }
+    }
+
+    /// Recognizes a format by its magic bytes, ID3 tags excepted (see read_format)
+    pub fn sniff(header: &[u8]) -> Option<AudioFormat> {
+        match header {
+            [b'f', b'L', b'a', b'C', ..] => Some(AudioFormat::Flac),
+            [b'O', b'g', b'g', b'S', ..] => Some(AudioFormat::Ogg),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => {
+                Some(AudioFormat::Wav)
+            }
+            [b'F', b'O', b'R', b'M', _, _, _, _, b'A', b'I', b'F', b'F' | b'C', ..] => {
+                Some(AudioFormat::Aiff)
+            }
+            [_, _, _, _, b'f', b't', b'y', b'p', ..] => Some(AudioFormat::Mp4),
+            // EBML header
+            [0x1A, 0x45, 0xDF, 0xA3, ..] => Some(AudioFormat::Mkv),
+            // MPEG audio frame sync, a zero layer being ADTS AAC
+            [0xFF, b1, ..] if b1 & 0xE0 == 0xE0 && b1 & 0x06 != 0 => Some(AudioFormat::Mp3),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AudioFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// Formats named by a file extension and found in its content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FormatDetection {
+    pub extension: Option<AudioFormat>,
+    pub content: Option<AudioFormat>,
+}
+
+impl FormatDetection {
+    /// Content format, extension one if the content isn't recognized
+    pub fn format(&self) -> Option<AudioFormat> {
+        self.content.or(self.extension)
+    }
+
+    /// True if the extension names another format than the content one
+    pub fn is_mismatch(&self) -> bool {
+        matches!((self.extension, self.content), (Some(extension), Some(content)) if extension != content)
+    }
+}
+
+/// Detects a file format from its extension and its first bytes
+pub fn detect_format(path: &Path) -> AudioResult<FormatDetection> {
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(AudioFormat::from_extension);
+    let content = read_format(&mut File::open(path)?)?;
+    Ok(FormatDetection { extension, content })
+}
+
+/// Sniffs the content format, looking past the ID3v2 tag which may precede any format
+pub fn read_format<R: Read + Seek>(reader: &mut R) -> io::Result<Option<AudioFormat>> {
+    let header = read_header(reader)?;
+    if !header.starts_with(b"ID3") || header.len() < 10 {
+        return Ok(AudioFormat::sniff(&header));
+    }
+    // Tag size is syncsafe, 7 bits per byte, without the header & optional footer
+    let size = header[6..10]
+        .iter()
+        .fold(0u64, |size, byte| (size << 7) | u64::from(byte & 0x7F));
+    let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+    reader.seek(SeekFrom::Start(10 + size + footer))?;
+    // ID3v2 tags are mostly found in mp3 files, whose first frame may follow padding
+    Ok(AudioFormat::sniff(&read_header(reader)?).or(Some(AudioFormat::Mp3)))
+}
+
+fn read_header<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    reader.take(HEADER_LEN as u64).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn it_sniffs_magic_bytes() {
+        assert_eq!(
+            AudioFormat::sniff(b"fLaC\0\0\0\x22"),
+            Some(AudioFormat::Flac)
+        );
+        assert_eq!(
+            AudioFormat::sniff(b"RIFF\x24\0\0\0WAVEfmt "),
+            Some(AudioFormat::Wav)
+        );
+        assert_eq!(
+            AudioFormat::sniff(b"\0\0\0\x20ftypM4A "),
+            Some(AudioFormat::Mp4)
+        );
+        assert_eq!(
+            AudioFormat::sniff(&[0xFF, 0xFB, 0x90]),
+            Some(AudioFormat::Mp3)
+        );
+        // ADTS AAC
+        assert_eq!(AudioFormat::sniff(&[0xFF, 0xF1, 0x50]), None);
+        assert_eq!(AudioFormat::sniff(b"RIFF\x24\0\0\0AVI "), None);
+        assert_eq!(
+            AudioFormat::sniff(b"FORM\0\0\x10\0AIFC"),
+            Some(AudioFormat::Aiff)
+        );
+        assert_eq!(
+            AudioFormat::sniff(&[0x1A, 0x45, 0xDF, 0xA3, 0x9F]),
+            Some(AudioFormat::Mkv)
+        );
+    }
+
+    #[test]
+    fn it_lists_supported_formats() {
+        let supported = AudioFormat::supported();
+        assert_eq!(supported[0], AudioFormat::Mp3);
+        assert_eq!(
+            supported.contains(&AudioFormat::Flac),
+            cfg!(feature = "flac")
+        );
+        assert_eq!(
+            supported.contains(&AudioFormat::Mp4),
+            cfg!(all(
+                feature = "isomp4",
+                any(feature = "aac", feature = "alac")
+            ))
+        );
+    }
+
+    #[test]
+    fn it_looks_past_id3_tag() {
+        // 3 bytes tag, then a FLAC stream
+        let mut flac = b"ID3\x04\0\0\0\0\0\x03abc".to_vec();
+        flac.extend_from_slice(b"fLaC\0\0\0\x22");
+        assert_eq!(
+            read_format(&mut Cursor::new(flac)).unwrap(),
+            Some(AudioFormat::Flac)
+        );
+        // 200 bytes tag, syncsafe size, then padding
+        let mut mp3 = b"ID3\x03\0\0\0\0\x01\x48".to_vec();
+        mp3.resize(250, 0);
+        assert_eq!(
+            read_format(&mut Cursor::new(mp3)).unwrap(),
+            Some(AudioFormat::Mp3)
+        );
+    }
+
+    #[test]
+    fn it_detects_mismatch() {
+        let detection = FormatDetection {
+            extension: AudioFormat::from_extension("MP3"),
+            content: Some(AudioFormat::Flac),
+        };
+        assert!(detection.is_mismatch());
+        assert_eq!(detection.format(), Some(AudioFormat::Flac));
+        let unknown_content = FormatDetection {
+            extension: Some(AudioFormat::Mp3),
+            content: None,
+        };
+        assert!(!unknown_content.is_mismatch());
+    }
+}
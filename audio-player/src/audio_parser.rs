@@ -1,43 +1,73 @@
 use crate::symphonia_wrapper;
+use crate::tag_writer;
 use crate::AudioTag;
-use std::error::Error;
-use std::path::Path;
+use crate::{
+    AudioResult, FileReport, Fingerprint, MetadataValue, SampleBlock, SampleLayout, TagEdit,
+};
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::mpsc::Sender;
 use std::thread::JoinHandle;
+use std::time::Duration;
 
-pub type TagsResult = Result<Vec<Option<String>>, Box<dyn Error>>;
+pub type TagsResult = AudioResult<Vec<Option<MetadataValue>>>;
+pub type AllTagsResult = AudioResult<Vec<(String, MetadataValue)>>;
+pub type VisualsResult = AudioResult<Vec<PathBuf>>;
+pub type ReportResult = AudioResult<FileReport>;
+pub type SampleStreamResult = AudioResult<Box<dyn Iterator<Item = AudioResult<SampleBlock>>>>;
 pub trait MetadataParserWrapper {
     /// DEBUG : Print audio tags
     fn print_tags(&self, audio_path: &Path);
     /// DEBUG : Print audio file metadata
-    fn print_metadata(&self, audio_path: &Path);
-    /// Get a file target metadata
-    fn get_metadata_string(&self, audio_path: &Path, target_metadata: &Vec<AudioTag>)
-        -> TagsResult;
+    fn print_metadata(&self, audio_path: &Path) -> AudioResult<()>;
+    /// Get audio file tracks, tags, visuals and cues, the data print_metadata prints
+    fn get_file_report(&self, audio_path: &Path) -> ReportResult;
+    /// Get a file target metadata, typed according to each tag
+    fn get_metadata(&self, audio_path: &Path, target_metadata: &Vec<AudioTag>) -> TagsResult;
+    /// Get a file tags by their container key i.e ["TXXX:CATALOGNUMBER", "ORIGINALYEAR"], case insensitive
+    fn get_raw_metadata(&self, audio_path: &Path, keys: &[String]) -> TagsResult;
+    /// Get every tag of a file as (container key, value) pairs
+    fn get_all_tags(&self, audio_path: &Path) -> AllTagsResult;
     /// DEBUG : Print audio file thumbnail
-    fn print_visuals(&self, audio_path: &Path);
-    /// TODO : try to extract this to a proper Wrapper
-    fn get_file_samples(&self, audio_path: &Path) -> Option<Box<Vec<f32>>>;
+    fn print_visuals(&self, audio_path: &Path) -> AudioResult<()>;
+    /// Writes embedded pictures into a folder, named by usage i.e "front-cover.jpg"
+    fn extract_visuals(&self, audio_path: &Path, out_dir: &Path) -> VisualsResult;
+    /// Sets or removes tags of MP3 (ID3v2) and FLAC (Vorbis comments) files, audio data is kept as is
+    fn write_tags(&self, audio_path: &Path, edits: &[TagEdit]) -> AudioResult<()>;
+    /// Decoded samples, one block per packet, to process a file in constant memory
+    fn get_sample_stream(&self, audio_path: &Path, layout: SampleLayout) -> SampleStreamResult;
+    /// Whole file decoded into interleaved samples, see get_sample_stream for long files
+    fn get_file_samples(&self, audio_path: &Path) -> AudioResult<Box<Vec<f32>>>;
+    /// Frames from `from` to `to` decoded into interleaved samples, each bound being the
+    /// start or end of file if unset. Packets before `from` are skipped, not decoded
+    fn get_file_range_samples(
+        &self,
+        audio_path: &Path,
+        from: Option<Duration>,
+        to: Option<Duration>,
+    ) -> AudioResult<Vec<f32>>;
+    /// Chromaprint like fingerprint of the first `length` of the file, mixed into mono
+    fn get_fingerprint(&self, audio_path: &Path, length: Duration) -> AudioResult<Fingerprint>;
+    /// Decoding errors are returned once the thread is joined
     fn get_live_sample(
         &self,
         audio_path: &Path,
         live_sample: Sender<(usize, usize, Vec<f32>)>,
         live_sample_written: &mut Rc<usize>,
-    ) -> JoinHandle<()>;
+    ) -> JoinHandle<AudioResult<()>>;
 }
 
 pub mod metadata_parser_builder {
     use crate::audio_parser::MetadataParserWrapper;
     use crate::audio_parser::SymphoniaWrapper;
-    use crate::AudioTag;
-    use std::error::Error;
+    use crate::{AudioResult, AudioTag, Fingerprint, SampleLayout, TagEdit};
     use std::path::Path;
     use std::rc::Rc;
     use std::sync::mpsc::Sender;
     use std::thread::JoinHandle;
+    use std::time::Duration;
 
-    use super::TagsResult;
+    use super::{AllTagsResult, ReportResult, SampleStreamResult, TagsResult, VisualsResult};
 
     /// Build a MetadataParser from current crate used
     pub fn build() -> Box<dyn MetadataParserWrapper> {
@@ -50,33 +80,57 @@ pub mod metadata_parser_builder {
     }
 
     impl MetadataParserWrapper for MetadataParser {
-        fn get_metadata_string(
-            &self,
-            audio_path: &Path,
-            target_metadata: &Vec<AudioTag>,
-        ) -> TagsResult {
-            self.wrapped
-                .get_metadata_string(audio_path, target_metadata)
+        fn get_metadata(&self, audio_path: &Path, target_metadata: &Vec<AudioTag>) -> TagsResult {
+            self.wrapped.get_metadata(audio_path, target_metadata)
+        }
+        fn get_raw_metadata(&self, audio_path: &Path, keys: &[String]) -> TagsResult {
+            self.wrapped.get_raw_metadata(audio_path, keys)
+        }
+        fn get_all_tags(&self, audio_path: &Path) -> AllTagsResult {
+            self.wrapped.get_all_tags(audio_path)
         }
-        fn print_metadata(&self, audio_path: &Path) {
-            self.wrapped.print_metadata(audio_path);
+        fn print_metadata(&self, audio_path: &Path) -> AudioResult<()> {
+            self.wrapped.print_metadata(audio_path)
+        }
+        fn get_file_report(&self, audio_path: &Path) -> ReportResult {
+            self.wrapped.get_file_report(audio_path)
         }
         fn print_tags(&self, audio_path: &Path) {
             self.wrapped.print_tags(audio_path);
         }
-        fn print_visuals(&self, audio_path: &Path) {
-            self.wrapped.print_visuals(audio_path);
+        fn print_visuals(&self, audio_path: &Path) -> AudioResult<()> {
+            self.wrapped.print_visuals(audio_path)
+        }
+        fn extract_visuals(&self, audio_path: &Path, out_dir: &Path) -> VisualsResult {
+            self.wrapped.extract_visuals(audio_path, out_dir)
+        }
+        fn write_tags(&self, audio_path: &Path, edits: &[TagEdit]) -> AudioResult<()> {
+            self.wrapped.write_tags(audio_path, edits)
+        }
+        fn get_sample_stream(&self, audio_path: &Path, layout: SampleLayout) -> SampleStreamResult {
+            self.wrapped.get_sample_stream(audio_path, layout)
         }
-        fn get_file_samples(&self, audio_path: &Path) -> Option<Box<Vec<f32>>> {
+        fn get_file_samples(&self, audio_path: &Path) -> AudioResult<Box<Vec<f32>>> {
             self.wrapped.get_file_samples(audio_path)
         }
+        fn get_file_range_samples(
+            &self,
+            audio_path: &Path,
+            from: Option<Duration>,
+            to: Option<Duration>,
+        ) -> AudioResult<Vec<f32>> {
+            self.wrapped.get_file_range_samples(audio_path, from, to)
+        }
+        fn get_fingerprint(&self, audio_path: &Path, length: Duration) -> AudioResult<Fingerprint> {
+            self.wrapped.get_fingerprint(audio_path, length)
+        }
 
         fn get_live_sample(
             &self,
             audio_path: &Path,
             live_sample: Sender<(usize, usize, Vec<f32>)>,
             live_sample_written: &mut Rc<usize>,
-        ) -> JoinHandle<()> {
+        ) -> JoinHandle<AudioResult<()>> {
             self.wrapped
                 .get_live_sample(audio_path, live_sample, live_sample_written)
         }
@@ -86,31 +140,59 @@ pub mod metadata_parser_builder {
 /// Symphonia lib wrapper
 pub struct SymphoniaWrapper;
 impl MetadataParserWrapper for SymphoniaWrapper {
-    fn get_metadata_string(
-        &self,
-        audio_path: &Path,
-        target_metadata: &Vec<AudioTag>,
-    ) -> TagsResult {
-        symphonia_wrapper::get_metadata_string(audio_path, target_metadata)
+    fn get_metadata(&self, audio_path: &Path, target_metadata: &Vec<AudioTag>) -> TagsResult {
+        symphonia_wrapper::get_metadata(audio_path, target_metadata)
+    }
+    fn get_raw_metadata(&self, audio_path: &Path, keys: &[String]) -> TagsResult {
+        symphonia_wrapper::get_raw_metadata(audio_path, keys)
     }
-    fn print_metadata(&self, audio_path: &Path) {
-        symphonia_wrapper::parse(audio_path);
+    fn get_all_tags(&self, audio_path: &Path) -> AllTagsResult {
+        symphonia_wrapper::get_all_tags(audio_path)
+    }
+    fn print_metadata(&self, audio_path: &Path) -> AudioResult<()> {
+        symphonia_wrapper::parse(audio_path)
+    }
+    fn get_file_report(&self, audio_path: &Path) -> ReportResult {
+        symphonia_wrapper::get_file_report(audio_path)
     }
     fn print_tags(&self, _audio_path: &Path) {
         //   symphonia_wrapper::print_tags(audio_path);
     }
-    fn print_visuals(&self, _audio_path: &Path) {
-        //   symphonia_wrapper::print_visuals(audio_path);
+    fn print_visuals(&self, audio_path: &Path) -> AudioResult<()> {
+        symphonia_wrapper::print_file_visuals(audio_path)
+    }
+    fn extract_visuals(&self, audio_path: &Path, out_dir: &Path) -> VisualsResult {
+        symphonia_wrapper::extract_visuals(audio_path, out_dir)
     }
-    fn get_file_samples(&self, audio_path: &Path) -> Option<Box<Vec<f32>>> {
+    /// Symphonia is read-only, tags are written by tag_writer
+    fn write_tags(&self, audio_path: &Path, edits: &[TagEdit]) -> AudioResult<()> {
+        tag_writer::write_tags(audio_path, edits)
+    }
+    fn get_sample_stream(&self, audio_path: &Path, layout: SampleLayout) -> SampleStreamResult {
+        Ok(Box::new(symphonia_wrapper::SampleStream::open(
+            audio_path, layout,
+        )?))
+    }
+    fn get_file_samples(&self, audio_path: &Path) -> AudioResult<Box<Vec<f32>>> {
         symphonia_wrapper::get_file_samples(audio_path)
     }
+    fn get_file_range_samples(
+        &self,
+        audio_path: &Path,
+        from: Option<Duration>,
+        to: Option<Duration>,
+    ) -> AudioResult<Vec<f32>> {
+        symphonia_wrapper::get_file_range_samples(audio_path, from, to)
+    }
+    fn get_fingerprint(&self, audio_path: &Path, length: Duration) -> AudioResult<Fingerprint> {
+        symphonia_wrapper::get_fingerprint(audio_path, length)
+    }
     fn get_live_sample(
         &self,
         audio_path: &Path,
         live_sample: Sender<(usize, usize, Vec<f32>)>,
         live_sample_written: &mut Rc<usize>,
-    ) -> JoinHandle<()> {
+    ) -> JoinHandle<AudioResult<()>> {
         symphonia_wrapper::get_live_sample(audio_path, live_sample, live_sample_written)
     }
 }
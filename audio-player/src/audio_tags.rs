@@ -3,12 +3,37 @@ pub enum AudioTag {
     // Tags
     Artist,
     Album,
+    AlbumArtist,
+    Composer,
+    Conductor,
     Bpm,
     Date,
     Genre,
     Lyrics,
     TrackNumber,
+    TrackTotal,
     TrackName,
+    DiscNumber,
+    DiscTotal,
+    Comment,
+    /// International Standard Recording Code
+    Isrc,
+    /// Record label
+    Label,
+    /// Set if the album is a compilation of various artists
+    Compilation,
+    MusicBrainzRecordingId,
+    /// MusicBrainz release, i.e album, identifier
+    MusicBrainzReleaseId,
+    MusicBrainzArtistId,
+    /// ReplayGain track gain, in dB
+    ReplayGainTrackGain,
+    /// ReplayGain track peak, linear
+    ReplayGainTrackPeak,
+    /// ReplayGain album gain, in dB
+    ReplayGainAlbumGain,
+    /// ReplayGain album peak, linear
+    ReplayGainAlbumPeak,
     // Metadatas
     Duration,
     FrameRate,
@@ -34,17 +59,34 @@ fn from_str_to_audio_tag(value: &str) -> AudioTag {
     match value {
         "artist" => AudioTag::Artist,
         "album" => AudioTag::Album,
+        "albumArtist" => AudioTag::AlbumArtist,
+        "composer" => AudioTag::Composer,
+        "conductor" => AudioTag::Conductor,
         "bpm" => AudioTag::Bpm,
         "date" => AudioTag::Date,
         "genre" => AudioTag::Genre,
         "lyrics" => AudioTag::Lyrics,
         "trackNumber" => AudioTag::TrackNumber,
+        "trackTotal" => AudioTag::TrackTotal,
         "trackName" => AudioTag::TrackName,
+        "discNumber" => AudioTag::DiscNumber,
+        "discTotal" => AudioTag::DiscTotal,
+        "comment" => AudioTag::Comment,
+        "isrc" => AudioTag::Isrc,
+        "label" => AudioTag::Label,
+        "compilation" => AudioTag::Compilation,
+        "musicBrainzRecordingId" => AudioTag::MusicBrainzRecordingId,
+        "musicBrainzReleaseId" => AudioTag::MusicBrainzReleaseId,
+        "musicBrainzArtistId" => AudioTag::MusicBrainzArtistId,
+        "replayGainTrackGain" => AudioTag::ReplayGainTrackGain,
+        "replayGainTrackPeak" => AudioTag::ReplayGainTrackPeak,
+        "replayGainAlbumGain" => AudioTag::ReplayGainAlbumGain,
+        "replayGainAlbumPeak" => AudioTag::ReplayGainAlbumPeak,
         // Metadatas
         "duration" => AudioTag::Duration,
         "frameRate" => AudioTag::FrameRate,
         "channelsNumber" => AudioTag::ChannelsNumber,
-        /// Total frames count
+        // Total frames count
         "totalFrames" => AudioTag::TotalFrames,
         _ => AudioTag::Unknown,
     }
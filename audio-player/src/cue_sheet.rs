@@ -0,0 +1,365 @@
+//!
+//! External CUE sheet parser, splitting single-file album rips into virtual tracks.
+//! Only commands locating tracks are read, i.e FILE, TRACK, TITLE, PERFORMER and INDEX 01.
+//!
+
+use log::warn;
+use serde::{Serialize, Serializer};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::{AudioError, AudioResult, AudioTag, MetadataValue};
+
+/// CUE sheet times are given in mm:ss:ff, with 75 frames per second
+const FRAMES_PER_SECOND: u64 = 75;
+
+/// Malformed sheets are reported as decode errors
+pub type CueResult<T> = AudioResult<T>;
+
+/// Track of a CUE sheet, played as a time range of its audio file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CueTrack {
+    /// Audio file holding the track
+    pub file: PathBuf,
+    pub number: u32,
+    pub title: Option<String>,
+    /// Track performer, album performer if not set
+    pub performer: Option<String>,
+    /// Album title
+    pub album: Option<String>,
+    /// Start time in the audio file, in seconds once serialized
+    #[serde(serialize_with = "serialize_seconds")]
+    pub start: Duration,
+    /// Next track start in the same file, the last track plays until the file end
+    #[serde(serialize_with = "serialize_optional_seconds")]
+    pub end: Option<Duration>,
+}
+
+impl CueTrack {
+    /// Values the CUE sheet gives for this track, others are read from the audio file
+    pub fn get_metadata(&self, tag: &AudioTag) -> Option<MetadataValue> {
+        match tag {
+            AudioTag::TrackName => self.title.clone().map(MetadataValue::Text),
+            AudioTag::Artist => self.performer.clone().map(MetadataValue::Text),
+            AudioTag::Album => self.album.clone().map(MetadataValue::Text),
+            AudioTag::TrackNumber => Some(MetadataValue::Integer(i64::from(self.number))),
+            AudioTag::Duration => self
+                .end
+                .map(|end| MetadataValue::Duration(end.saturating_sub(self.start))),
+            _ => None,
+        }
+    }
+}
+
+/// Track being parsed, its start is mandatory once the sheet is read
+struct ParsedTrack {
+    file: PathBuf,
+    number: u32,
+    title: Option<String>,
+    performer: Option<String>,
+    start: Option<Duration>,
+}
+
+/// Reads a CUE sheet file, its FILE paths being relative to its folder
+pub fn read(cue_path: &Path) -> CueResult<Vec<CueTrack>> {
+    // Sheets are often latin-1 encoded, invalid characters don't prevent reading times.
+    let content = fs::read(cue_path)?;
+    let base_dir = cue_path.parent().unwrap_or(Path::new(""));
+    parse(&String::from_utf8_lossy(&content), base_dir)
+}
+
+/// Parses a CUE sheet content
+///
+/// # Arguments
+/// * `content` - CUE sheet text
+/// * `base_dir` - folder FILE paths are relative to
+pub fn parse(content: &str, base_dir: &Path) -> CueResult<Vec<CueTrack>> {
+    let mut album = None;
+    let mut album_performer = None;
+    let mut file: Option<PathBuf> = None;
+    let mut tracks: Vec<ParsedTrack> = Vec::new();
+
+    for (line_idx, line) in content.lines().enumerate() {
+        let line = line.trim_start_matches('\u{feff}').trim();
+        let (command, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args = args.trim();
+        let invalid =
+            || AudioError::Decode(format!("invalid cue sheet line {}: {}", line_idx + 1, line));
+        match command.to_ascii_uppercase().as_str() {
+            "FILE" => file = Some(base_dir.join(parse_file_name(args))),
+            "TRACK" => {
+                let number = args
+                    .split_whitespace()
+                    .next()
+                    .and_then(|number| number.parse().ok())
+                    .ok_or_else(invalid)?;
+                tracks.push(ParsedTrack {
+                    file: file.clone().ok_or_else(invalid)?,
+                    number,
+                    title: None,
+                    performer: None,
+                    start: None,
+                });
+            }
+            // Before the first TRACK, TITLE and PERFORMER describe the album.
+            "TITLE" => match tracks.last_mut() {
+                Some(track) => track.title = Some(unquote(args)),
+                None => album = Some(unquote(args)),
+            },
+            "PERFORMER" => match tracks.last_mut() {
+                Some(track) => track.performer = Some(unquote(args)),
+                None => album_performer = Some(unquote(args)),
+            },
+            // INDEX 00 is the pregap, played at the end of the previous track.
+            "INDEX" => {
+                let (index, time) = args.split_once(char::is_whitespace).ok_or_else(invalid)?;
+                if index.parse::<u32>().ok() == Some(1) {
+                    let track = tracks.last_mut().ok_or_else(invalid)?;
+                    track.start = Some(parse_time(time.trim()).ok_or_else(invalid)?);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let starts = tracks
+        .iter()
+        .map(|track| {
+            track.start.ok_or_else(|| {
+                AudioError::Decode(format!("cue sheet track {} has no INDEX 01", track.number))
+            })
+        })
+        .collect::<CueResult<Vec<Duration>>>()?;
+    Ok(tracks
+        .iter()
+        .enumerate()
+        .map(|(idx, track)| CueTrack {
+            file: track.file.clone(),
+            number: track.number,
+            title: track.title.clone(),
+            performer: track.performer.clone().or_else(|| album_performer.clone()),
+            album: album.clone(),
+            start: starts[idx],
+            end: tracks
+                .get(idx + 1)
+                .filter(|next| next.file == track.file)
+                .map(|_| starts[idx + 1]),
+        })
+        .collect())
+}
+
+/// Virtual tracks of an audio file, read from CUE sheets of its folder.
+/// Sheets may still name the file before it was encoded, i.e "album.wav" for "album.flac" once
+/// "album.wav" is gone
+///
+/// # Returns
+/// tracks of the first sheet describing this file, empty if there is none
+pub fn find_cue_tracks(audio_path: &Path) -> CueResult<Vec<CueTrack>> {
+    let sheets = read_folder(folder_of(audio_path))?;
+    Ok(find_tracks(&sheets, audio_path))
+}
+
+/// Folder holding an audio file and its CUE sheets
+pub fn folder_of(audio_path: &Path) -> &Path {
+    match audio_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+/// Reads every CUE sheet of a folder, sorted by path. Broken sheets are skipped with a warning.
+///
+/// # Returns
+/// tracks of each sheet
+pub fn read_folder(dir: &Path) -> CueResult<Vec<Vec<CueTrack>>> {
+    let mut cue_paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let cue_path = entry?.path();
+        let is_cue = cue_path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("cue"));
+        if is_cue {
+            cue_paths.push(cue_path);
+        }
+    }
+    cue_paths.sort();
+    Ok(cue_paths
+        .iter()
+        .filter_map(|cue_path| match read(cue_path) {
+            Ok(tracks) => Some(tracks),
+            Err(err) => {
+                warn!("skipping cue sheet {:?}: {}", cue_path, err);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Virtual tracks of an audio file, among CUE sheets read by `read_folder`
+///
+/// # Returns
+/// tracks of the first sheet describing this file, empty if there is none
+pub fn find_tracks(sheets: &[Vec<CueTrack>], audio_path: &Path) -> Vec<CueTrack> {
+    for sheet in sheets {
+        let tracks: Vec<CueTrack> = sheet
+            .iter()
+            .filter(|track| is_same_file(&track.file, audio_path))
+            .map(|track| CueTrack {
+                file: audio_path.to_path_buf(),
+                ..track.clone()
+            })
+            .collect();
+        if !tracks.is_empty() {
+            return tracks;
+        }
+    }
+    Vec::new()
+}
+
+/// Files are matched by name, by stem only if the sheet one is missing, so that an encode
+/// doesn't take the tracks of its source sitting in the same folder
+fn is_same_file(cue_file: &Path, audio_path: &Path) -> bool {
+    if cue_file.file_name() == audio_path.file_name() {
+        return true;
+    }
+    cue_file.file_stem() == audio_path.file_stem() && !cue_file.exists()
+}
+
+/// FILE arguments are the file name, quoted if it has spaces, then its type i.e WAVE
+fn parse_file_name(args: &str) -> String {
+    if args.starts_with('"') {
+        return unquote(args);
+    }
+    match args.rsplit_once(char::is_whitespace) {
+        Some((name, _file_type)) => name.trim().to_string(),
+        None => args.to_string(),
+    }
+}
+
+fn unquote(args: &str) -> String {
+    match args.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or(quoted).to_string(),
+        None => args.to_string(),
+    }
+}
+
+/// Parses "mm:ss:ff" times, minutes may exceed 59
+fn parse_time(time: &str) -> Option<Duration> {
+    let mut parts = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (mins, secs, frames) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || secs >= 60 || frames >= FRAMES_PER_SECOND {
+        return None;
+    }
+    let frames = (mins * 60 + secs) * FRAMES_PER_SECOND + frames;
+    Some(Duration::from_nanos(
+        frames * 1_000_000_000 / FRAMES_PER_SECOND,
+    ))
+}
+
+fn serialize_seconds<S: Serializer>(time: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(time.as_secs_f64())
+}
+
+fn serialize_optional_seconds<S: Serializer>(
+    time: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serialize_seconds(time, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_dir;
+
+    const SHEET: &str = r#"REM GENRE Electronica
+PERFORMER "Boards of Canada"
+TITLE "Geogaddi"
+FILE "Geogaddi.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "Ready Lets Go"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Music Is Math"
+    PERFORMER "BoC"
+    INDEX 00 00:59:10
+    INDEX 01 01:00:37
+"#;
+
+    #[test]
+    fn it_parses_tracks() {
+        let tracks = parse(SHEET, Path::new("/music")).unwrap();
+
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[0].file, PathBuf::from("/music/Geogaddi.wav"));
+        assert_eq!(tracks[0].title.as_deref(), Some("Ready Lets Go"));
+        assert_eq!(tracks[0].performer.as_deref(), Some("Boards of Canada"));
+        assert_eq!(tracks[0].album.as_deref(), Some("Geogaddi"));
+        // 37 frames of 1/75 second, pregap excluded
+        assert_eq!(tracks[0].end, Some(Duration::from_nanos(60_493_333_333)));
+        assert_eq!(tracks[1].start, Duration::from_nanos(60_493_333_333));
+        assert_eq!(tracks[1].performer.as_deref(), Some("BoC"));
+        assert_eq!(tracks[1].end, None);
+        assert_eq!(
+            tracks[1].get_metadata(&AudioTag::TrackNumber),
+            Some(MetadataValue::Integer(2))
+        );
+    }
+
+    #[test]
+    fn it_rejects_track_without_start() {
+        let sheet = "FILE \"a.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 00 00:00:00\n";
+        assert!(parse(sheet, Path::new("")).is_err());
+        assert!(parse("TRACK 01 AUDIO\n", Path::new("")).is_err());
+        assert_eq!(parse_time("01:60:00"), None);
+    }
+
+    #[test]
+    fn it_finds_tracks_of_encoded_file() {
+        let dir = temp_dir("cue-sheet");
+        fs::write(dir.join("Geogaddi.cue"), SHEET).unwrap();
+        let audio_path = dir.join("Geogaddi.flac");
+
+        let tracks = find_cue_tracks(&audio_path).unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[1].file, audio_path);
+        assert!(find_cue_tracks(&dir.join("other.flac")).unwrap().is_empty());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn it_keeps_tracks_of_named_file_among_siblings() {
+        let dir = temp_dir("cue-sheet-siblings");
+        fs::write(dir.join("Geogaddi.cue"), SHEET).unwrap();
+        fs::write(dir.join("Geogaddi.wav"), b"").unwrap();
+        let sheets = read_folder(&dir).unwrap();
+
+        assert_eq!(find_tracks(&sheets, &dir.join("Geogaddi.wav")).len(), 2);
+        assert!(find_tracks(&sheets, &dir.join("Geogaddi.flac")).is_empty());
+        assert!(find_tracks(&sheets, &dir.join("Geogaddi.mp3")).is_empty());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn it_skips_broken_sheets() {
+        let dir = temp_dir("cue-sheet-broken");
+        fs::write(
+            dir.join("a-broken.cue"),
+            "FILE \"Geogaddi.wav\" WAVE\n  TRACK 01 AUDIO\n",
+        )
+        .unwrap();
+        fs::write(dir.join("b-Geogaddi.cue"), SHEET).unwrap();
+
+        let sheets = read_folder(&dir).unwrap();
+        assert_eq!(sheets.len(), 1);
+        assert_eq!(find_tracks(&sheets, &dir.join("Geogaddi.flac")).len(), 2);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,8 +1,40 @@
 mod audio_parser;
 mod symphonia_wrapper;
+#[cfg(test)]
+mod test_util;
 pub use audio_parser::metadata_parser_builder as MetadataParserBuilder;
 pub use audio_parser::MetadataParserWrapper;
+pub use symphonia_wrapper::playQueue;
 pub use symphonia_wrapper::playTrack;
+pub use symphonia_wrapper::CrossfadeCurve;
+pub use symphonia_wrapper::OutputBackend;
+pub use symphonia_wrapper::PlaybackOptions;
+pub use symphonia_wrapper::Player;
+pub use symphonia_wrapper::ReplayGain;
+pub use symphonia_wrapper::ReplayGainMode;
+pub use symphonia_wrapper::ResampleQuality;
+pub use symphonia_wrapper::{export_wav, WavBitDepth, WavExportOptions};
+pub use symphonia_wrapper::{
+    get_file_channels, get_file_downmix, Downmix, SampleBlock, SampleLayout, SampleStream,
+};
+pub use symphonia_wrapper::{get_fingerprint, Fingerprint};
+pub use symphonia_wrapper::{scan_album, scan_track, AlbumLoudness, Loudness, LoudnessResult};
+pub use symphonia_wrapper::{
+    CuePointReport, CueReport, FileReport, TagReport, TrackReport, VisualReport,
+};
+pub mod audio_error;
+pub mod audio_format;
 pub mod audio_tags;
-pub use audio_parser::TagsResult;
+pub mod cue_sheet;
+pub mod metadata_value;
+pub mod tag_writer;
+pub mod time_parser;
+pub use audio_error::{AudioError, AudioResult};
+pub use audio_format::{AudioFormat, FormatDetection};
+pub use audio_parser::{
+    AllTagsResult, ReportResult, SampleStreamResult, TagsResult, VisualsResult,
+};
 pub use audio_tags::AudioTag;
+pub use cue_sheet::CueTrack;
+pub use metadata_value::MetadataValue;
+pub use tag_writer::TagEdit;
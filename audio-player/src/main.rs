@@ -1,18 +1,16 @@
-mod audio_parser;
-mod audio_tags;
-mod symphonia_wrapper;
-use args::Cli;
+use args::{Cli, OutputBackendKind, ReportFormat};
+use audio_player::{
+    cue_sheet, playQueue, AudioTag, CueTrack, Fingerprint, MetadataParserBuilder,
+    MetadataParserWrapper, OutputBackend, PlaybackOptions, WavExportOptions,
+};
 use log::error;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 use std::sync::mpsc::channel;
 use std::thread::{self};
-use std::{
-    path::Path,
-    sync::{Arc, Mutex},
-};
+use std::time::Duration;
 
 mod args;
-use crate::audio_tags::AudioTag;
 use clap::Parser;
 
 fn main() {
@@ -31,63 +29,266 @@ fn main() {
 }
 
 fn run(args: &Cli) -> Result<i32, Box<dyn std::error::Error>> {
-    let music_path = Path::new(&args.input);
-
-    if args.analyze {
-        symphonia_wrapper::parse(music_path);
-    }
-    if args.full_audio_sample {
-        process_audio_sample(music_path);
-    }
-    if args.live_audio_sample {
-        process_live_audio_sample(music_path);
+    let metadata_parser = MetadataParserBuilder::build();
+    let export_options = WavExportOptions {
+        bit_depth: args.bit_depth,
+        sample_rate: args.sample_rate,
+        resample_quality: args.resample_quality,
+        from: args.from,
+        to: args.to,
+    };
+    if let Some(wav_path) = args.export_wav.as_deref() {
+        if args.input.len() > 1 && !wav_path.is_dir() {
+            return Err(format!("{:?} must be a folder to export several inputs", wav_path).into());
+        }
     }
-    if let Some(tags) = args.tag.as_deref() {
-        process_tag(music_path, tags);
+    let mut first_fingerprint = None;
+
+    for music_path in args.input.iter().map(PathBuf::as_path) {
+        if args.analyze {
+            process_analyze(music_path, metadata_parser.as_ref(), args.format)?;
+        }
+        if args.full_audio_sample {
+            process_audio_sample(music_path, metadata_parser.as_ref(), args.from, args.to);
+        }
+        if let Some(wav_path) = args.export_wav.as_deref() {
+            process_export_wav(music_path, wav_path, &export_options)?;
+        }
+        if args.fingerprint {
+            process_fingerprint(
+                music_path,
+                metadata_parser.as_ref(),
+                args.fingerprint_length,
+                &mut first_fingerprint,
+            )?;
+        }
+        if args.live_audio_sample {
+            process_live_audio_sample(music_path, metadata_parser.as_ref())?;
+        }
+        if let Some(tags) = args.tag.as_deref() {
+            process_tag(music_path, metadata_parser.as_ref(), tags, args.format)?;
+        }
+        if let Some(keys) = args.raw_tag.as_deref() {
+            process_raw_tag(music_path, metadata_parser.as_ref(), keys)?;
+        }
+        if args.all_tags {
+            process_all_tags(music_path, metadata_parser.as_ref())?;
+        }
+        if let Some(out_dir) = args.extract_cover.as_deref() {
+            let out_dir = match music_path.file_stem() {
+                Some(name) if args.input.len() > 1 => out_dir.join(name),
+                _ => out_dir.to_path_buf(),
+            };
+            process_extract_cover(music_path, metadata_parser.as_ref(), &out_dir)?;
+        }
     }
     if args.play {
-        return process_play(music_path);
+        let cue_track = match args.cue_track {
+            Some(number) => Some(get_cue_track(&args.input[0], number)?),
+            None => None,
+        };
+        let options = PlaybackOptions {
+            output_backend: get_output_backend(args)?,
+            resample_quality: args.resample_quality,
+            volume_db: args.volume,
+            muted: args.mute,
+            replay_gain: args.replaygain,
+            crossfade: args.crossfade,
+            crossfade_curve: args.crossfade_curve,
+            track: args.track,
+            seek: cue_track.as_ref().map(|t| t.start).or(args.seek),
+            end: cue_track.as_ref().and_then(|t| t.end),
+            ..Default::default()
+        };
+        return process_play(&args.input, options);
     }
     Ok(1)
 }
 
-fn process_audio_sample(music_path: &Path) {
-    let samples_from_file = symphonia_wrapper::get_file_samples(music_path);
-    if let Some(samples) = samples_from_file {
-        println!("Audio samples : {}", samples.len());
-    } else {
-        println!("No sample found for {:?}", music_path);
+fn get_cue_track(music_path: &Path, number: u32) -> Result<CueTrack, Box<dyn std::error::Error>> {
+    let cue_track = cue_sheet::find_cue_tracks(music_path)?
+        .into_iter()
+        .find(|cue_track| cue_track.number == number)
+        .ok_or_else(|| format!("no cue track {} found for {:?}", number, music_path))?;
+    println!(
+        "Playing cue track {:0>2} {}",
+        cue_track.number,
+        cue_track.title.as_deref().unwrap_or("")
+    );
+    Ok(cue_track)
+}
+
+fn get_output_backend(args: &Cli) -> Result<OutputBackend, Box<dyn std::error::Error>> {
+    Ok(match args.output_backend {
+        OutputBackendKind::Device => OutputBackend::Device,
+        OutputBackendKind::Wav => match args.output_file.as_deref() {
+            Some(output_file) => OutputBackend::Wav(output_file.to_path_buf()),
+            None => return Err("wav output backend requires --output-file".into()),
+        },
+        OutputBackendKind::Null => OutputBackend::Null,
+    })
+}
+
+fn process_audio_sample(
+    music_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    from: Option<Duration>,
+    to: Option<Duration>,
+) {
+    let samples_from_file = metadata_parser.get_file_range_samples(music_path, from, to);
+    match samples_from_file {
+        Ok(samples) => println!("Audio samples : {}", samples.len()),
+        Err(err) => println!("No sample found for {:?}: {}", music_path, err),
     }
 }
 
-fn process_live_audio_sample(music_path: &Path) {
+fn process_export_wav(
+    music_path: &Path,
+    wav_path: &Path,
+    options: &WavExportOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let wav_path = match music_path.file_stem() {
+        Some(name) if wav_path.is_dir() => wav_path.join(name).with_extension("wav"),
+        _ => wav_path.to_path_buf(),
+    };
+    let frames = audio_player::export_wav(music_path, &wav_path, options)?;
+    println!("{} frames written into {:?}", frames, wav_path);
+    Ok(())
+}
+
+/// Prints the fingerprint, and its similarity to the first input one if any
+fn process_fingerprint(
+    music_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    length: Duration,
+    first_fingerprint: &mut Option<Fingerprint>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let fingerprint = metadata_parser.get_fingerprint(music_path, length)?;
+    println!(
+        "fingerprint of {:?} over {:.1}s = {}",
+        music_path,
+        fingerprint.duration.as_secs_f64(),
+        fingerprint.encode()
+    );
+    match first_fingerprint
+        .as_ref()
+        .map(|first| first.similarity(&fingerprint))
+    {
+        Some(Some(similarity)) => println!("similarity to first input = {:.3}", similarity),
+        Some(None) => println!("similarity to first input = none, one fingerprint is empty"),
+        None => *first_fingerprint = Some(fingerprint),
+    }
+    Ok(())
+}
+
+fn process_live_audio_sample(
+    music_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+) -> Result<(), Box<dyn std::error::Error>> {
     let (tx, rx) = channel::<(usize, usize, Vec<f32>)>();
 
     let mut live_sample_written = Rc::new(1);
 
-    let player_h = symphonia_wrapper::get_live_sample(music_path, tx, &mut live_sample_written);
+    let player_h = metadata_parser.get_live_sample(music_path, tx, &mut live_sample_written);
     let listener_h = thread::spawn(move || {
         for (packet_id, _buffer_len, _buffer_data) in rx {
             println!("{:?}", packet_id);
         }
     });
-    player_h.join().unwrap();
-    listener_h.join().unwrap();
+    let decoded = player_h
+        .join()
+        .map_err(|_| "decoding thread panicked".to_string())?;
+    listener_h
+        .join()
+        .map_err(|_| "listening thread panicked".to_string())?;
+    Ok(decoded?)
 }
 
-fn process_tag(music_path: &Path, tags: &[String]) {
-    let iter_tags = tags.into_iter();
+fn process_analyze(
+    music_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    format: ReportFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        ReportFormat::Json => println!(
+            "{}",
+            metadata_parser.get_file_report(music_path)?.to_json()?
+        ),
+        ReportFormat::Text => metadata_parser.print_metadata(music_path)?,
+    }
+    Ok(())
+}
+
+fn process_tag(
+    music_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    tags: &[String],
+    format: ReportFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let iter_tags = tags.iter();
     let tags_list: Vec<AudioTag> = iter_tags.map(AudioTag::from).collect();
-    let res = symphonia_wrapper::get_metadata_string(music_path, &tags_list).unwrap();
-    println!("tag :{:?}= {:?}", tags_list, res);
+    let values = metadata_parser.get_metadata(music_path, &tags_list)?;
+    if format == ReportFormat::Json {
+        // Tags are keyed as requested, values keep their type
+        let tags_map: serde_json::Map<String, serde_json::Value> = tags
+            .iter()
+            .zip(values)
+            .map(|(tag, value)| Ok((tag.clone(), serde_json::to_value(value)?)))
+            .collect::<serde_json::Result<_>>()?;
+        let report = serde_json::json!({ "path": music_path, "tags": tags_map });
+        println!("{}", report);
+    } else {
+        let res: Vec<Option<String>> = values
+            .iter()
+            .map(|value| value.as_ref().map(|value| value.to_string()))
+            .collect();
+        println!("tag :{:?}= {:?}", tags_list, res);
+    }
+    Ok(())
 }
 
-fn process_play(music_path: &Path) -> Result<i32, Box<dyn std::error::Error>> {
-    let temp_param_1 = Arc::new(Mutex::new((0_u64, (0_u64, 0_u64, 0_f64))));
-    let temp_param_2 = Arc::new(Mutex::new(true));
-    let code = match symphonia_wrapper::playTrack(music_path, temp_param_1, temp_param_2) {
-        Ok(code) => Ok(code),
-        Err(err) => Err(err.into()),
-    };
-    return code;
+fn process_raw_tag(
+    music_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    keys: &[String],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let res: Vec<Option<String>> = metadata_parser
+        .get_raw_metadata(music_path, keys)?
+        .iter()
+        .map(|value| value.as_ref().map(|value| value.to_string()))
+        .collect();
+    println!("raw tag :{:?}= {:?}", keys, res);
+    Ok(())
+}
+
+fn process_extract_cover(
+    music_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+    out_dir: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let written = metadata_parser.extract_visuals(music_path, out_dir)?;
+    if written.is_empty() {
+        println!("No cover found for {:?}", music_path);
+    }
+    for visual_path in written {
+        println!("cover written {:?}", visual_path);
+    }
+    Ok(())
+}
+
+fn process_all_tags(
+    music_path: &Path,
+    metadata_parser: &dyn MetadataParserWrapper,
+) -> Result<(), Box<dyn std::error::Error>> {
+    for (key, value) in metadata_parser.get_all_tags(music_path)? {
+        println!("{} = {}", key, value);
+    }
+    Ok(())
+}
+
+fn process_play(
+    music_paths: &[PathBuf],
+    options: PlaybackOptions,
+) -> Result<i32, Box<dyn std::error::Error>> {
+    Ok(playQueue(music_paths, options)?)
 }
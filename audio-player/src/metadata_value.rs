@@ -0,0 +1,313 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::time::Duration;
+
+use serde::{Serialize, Serializer};
+use symphonia::core::meta::Value;
+
+use crate::AudioTag;
+
+/// Typed content of an AudioTag.
+/// Numeric values compare numerically whatever their variant, i.e Integer(44100) == Float(44100.0)
+#[derive(Debug, Clone)]
+pub enum MetadataValue {
+    Integer(i64),
+    Float(f64),
+    Text(String),
+    Duration(Duration),
+    /// Release date, as precise as the tag is
+    Date {
+        year: i32,
+        month: Option<u8>,
+        day: Option<u8>,
+    },
+    Binary(Vec<u8>),
+}
+
+impl MetadataValue {
+    /// Parses a tag text content into the type expected for this tag.
+    /// Falls back to Text if the content doesn't match this type.
+    pub fn parse(tag: &AudioTag, text: &str) -> MetadataValue {
+        let text = text.trim();
+        let parsed = match tag {
+            AudioTag::Bpm => parse_number(text),
+            // Numbers may be followed by the total count, i.e "3/12"
+            AudioTag::TrackNumber | AudioTag::DiscNumber => {
+                parse_integer(text.split('/').next().unwrap_or(text))
+            }
+            AudioTag::TrackTotal
+            | AudioTag::DiscTotal
+            | AudioTag::Compilation
+            | AudioTag::FrameRate
+            | AudioTag::ChannelsNumber
+            | AudioTag::TotalFrames => parse_integer(text),
+            AudioTag::ReplayGainTrackGain
+            | AudioTag::ReplayGainTrackPeak
+            | AudioTag::ReplayGainAlbumGain
+            | AudioTag::ReplayGainAlbumPeak => parse_gain(text),
+            AudioTag::Date => parse_date(text),
+            AudioTag::Duration => parse_duration(text),
+            _ => None,
+        };
+        parsed.unwrap_or_else(|| MetadataValue::Text(text.to_string()))
+    }
+
+    /// Converts a symphonia tag value, text is parsed into the type expected for this tag.
+    pub fn from_tag_value(tag: &AudioTag, value: &Value) -> MetadataValue {
+        match value {
+            Value::Binary(buf) => MetadataValue::Binary(buf.to_vec()),
+            Value::Boolean(boolean) => MetadataValue::Integer(i64::from(*boolean)),
+            Value::Flag => MetadataValue::Integer(1),
+            Value::Float(float) => MetadataValue::Float(*float),
+            Value::SignedInt(int) => MetadataValue::Integer(*int),
+            Value::String(string) => MetadataValue::parse(tag, string),
+            Value::UnsignedInt(uint) => MetadataValue::Integer(*uint as i64),
+        }
+    }
+
+    /// Numeric value, durations in seconds
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            MetadataValue::Integer(int) => Some(*int as f64),
+            MetadataValue::Float(float) => Some(*float),
+            MetadataValue::Duration(duration) => Some(duration.as_secs_f64()),
+            _ => None,
+        }
+    }
+
+    /// Total order for sorting values of any type: numbers first, ordered numerically,
+    /// then durations, dates, texts and binaries. Equal to partial_cmp when it gives an order
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (
+                MetadataValue::Integer(_) | MetadataValue::Float(_),
+                MetadataValue::Integer(_) | MetadataValue::Float(_),
+            ) => self.as_f64().unwrap().total_cmp(&other.as_f64().unwrap()),
+            _ if self.rank() != other.rank() => self.rank().cmp(&other.rank()),
+            _ => self.partial_cmp(other).unwrap_or(Ordering::Equal),
+        }
+    }
+
+    /// Variant position in total_cmp order
+    fn rank(&self) -> u8 {
+        match self {
+            MetadataValue::Integer(_) | MetadataValue::Float(_) => 0,
+            MetadataValue::Duration(_) => 1,
+            MetadataValue::Date { .. } => 2,
+            MetadataValue::Text(_) => 3,
+            MetadataValue::Binary(_) => 4,
+        }
+    }
+
+    /// Integer value, floats are accepted if they have no fractional part
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            MetadataValue::Integer(int) => Some(*int),
+            MetadataValue::Float(float) if float.fract() == 0.0 => Some(*float as i64),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for MetadataValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for MetadataValue {
+    /// Values of different types aren't comparable, numbers excepted
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (MetadataValue::Integer(a), MetadataValue::Integer(b)) => Some(a.cmp(b)),
+            (MetadataValue::Text(a), MetadataValue::Text(b)) => Some(a.cmp(b)),
+            (MetadataValue::Duration(a), MetadataValue::Duration(b)) => Some(a.cmp(b)),
+            (
+                MetadataValue::Date { year, month, day },
+                MetadataValue::Date {
+                    year: other_year,
+                    month: other_month,
+                    day: other_day,
+                },
+            ) => Some((year, month, day).cmp(&(other_year, other_month, other_day))),
+            (MetadataValue::Binary(a), MetadataValue::Binary(b)) => Some(a.cmp(b)),
+            (MetadataValue::Integer(_) | MetadataValue::Float(_), _) => {
+                self.as_f64()?.partial_cmp(&other.as_f64()?)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MetadataValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataValue::Integer(int) => write!(f, "{}", int),
+            MetadataValue::Float(float) => write!(f, "{}", float),
+            MetadataValue::Text(text) => f.write_str(text),
+            MetadataValue::Duration(duration) => {
+                let seconds = duration.as_secs();
+                let hours = seconds / (60 * 60);
+                let mins = (seconds % (60 * 60)) / 60;
+                let secs = (seconds % 60) as f64 + f64::from(duration.subsec_nanos()) / 1e9;
+                write!(f, "{}:{:0>2}:{:0>6.3}", hours, mins, secs)
+            }
+            MetadataValue::Date { year, month, day } => {
+                write!(f, "{:04}", year)?;
+                if let Some(month) = month {
+                    write!(f, "-{:02}", month)?;
+                    if let Some(day) = day {
+                        write!(f, "-{:02}", day)?;
+                    }
+                }
+                Ok(())
+            }
+            MetadataValue::Binary(buf) => {
+                for byte in buf {
+                    write!(f, "\\0x{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Serialize for MetadataValue {
+    /// Numbers as numbers, durations in seconds, other values as displayed
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MetadataValue::Integer(int) => serializer.serialize_i64(*int),
+            MetadataValue::Float(float) => serializer.serialize_f64(*float),
+            MetadataValue::Duration(duration) => serializer.serialize_f64(duration.as_secs_f64()),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+fn parse_integer(text: &str) -> Option<MetadataValue> {
+    text.trim().parse::<i64>().ok().map(MetadataValue::Integer)
+}
+
+fn parse_number(text: &str) -> Option<MetadataValue> {
+    parse_integer(text).or_else(|| text.parse::<f64>().ok().map(MetadataValue::Float))
+}
+
+/// Parses gains such as "-6.54 dB" or peaks such as "0.988553"
+fn parse_gain(text: &str) -> Option<MetadataValue> {
+    let text = text
+        .strip_suffix("dB")
+        .or_else(|| text.strip_suffix("db"))
+        .unwrap_or(text);
+    text.trim().parse::<f64>().ok().map(MetadataValue::Float)
+}
+
+/// Parses "YYYY", "YYYY-MM" or "YYYY-MM-DD" dates, time part being ignored
+fn parse_date(text: &str) -> Option<MetadataValue> {
+    let date = text.split(['T', ' ']).next()?;
+    let mut parts = date.split('-');
+    let year = parts.next().filter(|year| year.len() == 4)?.parse().ok()?;
+    let month = match parts.next() {
+        Some(month) => Some(month.parse::<u8>().ok().filter(|m| (1..=12).contains(m))?),
+        None => None,
+    };
+    let day = match parts.next() {
+        Some(day) => Some(day.parse::<u8>().ok().filter(|d| (1..=31).contains(d))?),
+        None => None,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(MetadataValue::Date { year, month, day })
+}
+
+/// Parses "h:mm:ss.sss", "m:ss" or seconds durations
+fn parse_duration(text: &str) -> Option<MetadataValue> {
+    let mut seconds = 0.0;
+    for part in text.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Duration::try_from_secs_f64(seconds)
+        .ok()
+        .map(MetadataValue::Duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_typed_values() {
+        assert_eq!(
+            MetadataValue::parse(&AudioTag::TrackNumber, "3/12"),
+            MetadataValue::Integer(3)
+        );
+        assert_eq!(
+            MetadataValue::parse(&AudioTag::ReplayGainTrackGain, "-6.54 dB"),
+            MetadataValue::Float(-6.54)
+        );
+        assert_eq!(
+            MetadataValue::parse(&AudioTag::Date, "2004-05"),
+            MetadataValue::Date {
+                year: 2004,
+                month: Some(5),
+                day: None
+            }
+        );
+        assert_eq!(
+            MetadataValue::parse(&AudioTag::Duration, "0:03:25.500"),
+            MetadataValue::Duration(Duration::from_millis(205500))
+        );
+        assert_eq!(
+            MetadataValue::parse(&AudioTag::Date, "summer 2004"),
+            MetadataValue::Text("summer 2004".to_string())
+        );
+    }
+
+    #[test]
+    fn it_compares_numbers_numerically() {
+        let rate = MetadataValue::parse(&AudioTag::FrameRate, "44100");
+        assert_eq!(rate, MetadataValue::Float(44100.0));
+        assert!(MetadataValue::Integer(9) < MetadataValue::Integer(10));
+        // as text, "9" would be greater than "10"
+        assert!(MetadataValue::Text("9".into()) > MetadataValue::Text("10".into()));
+        assert_eq!(
+            MetadataValue::Integer(1).partial_cmp(&MetadataValue::Text("1".into())),
+            None
+        );
+    }
+
+    #[test]
+    fn it_sorts_mixed_values() {
+        let mut values = [
+            MetadataValue::parse(&AudioTag::Date, "summer 2004"),
+            MetadataValue::parse(&AudioTag::Date, "2004"),
+            MetadataValue::Text("A1".into()),
+            MetadataValue::Float(f64::NAN),
+            MetadataValue::Integer(3),
+            MetadataValue::Float(2.5),
+        ];
+        values.sort_by(MetadataValue::total_cmp);
+        assert_eq!(values[0], MetadataValue::Float(2.5));
+        assert_eq!(values[1], MetadataValue::Integer(3));
+        assert!(matches!(values[2], MetadataValue::Float(nan) if nan.is_nan()));
+        assert_eq!(values[3], MetadataValue::parse(&AudioTag::Date, "2004"));
+        assert_eq!(values[4], MetadataValue::Text("A1".into()));
+        assert_eq!(
+            MetadataValue::Integer(3).total_cmp(&MetadataValue::Float(3.0)),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn it_displays_as_tag_string() {
+        assert_eq!(MetadataValue::Integer(44100).to_string(), "44100");
+        assert_eq!(
+            MetadataValue::Duration(Duration::from_millis(205500)).to_string(),
+            "0:03:25.500"
+        );
+        assert_eq!(
+            MetadataValue::parse(&AudioTag::Date, "2004-05-12").to_string(),
+            "2004-05-12"
+        );
+    }
+}
@@ -1,8 +1,33 @@
 mod commons;
+mod file_report;
+mod fingerprint;
+mod gain;
+mod loudness;
 mod metadata_parser;
+mod mixer;
 mod output;
 mod player;
+mod resampler;
+mod sample_stream;
+mod wav_export;
+pub use file_report::{
+    CuePointReport, CueReport, FileReport, TagReport, TrackReport, VisualReport,
+};
+pub use fingerprint::{get_fingerprint, Fingerprint};
+pub use gain::ReplayGain;
+pub use gain::ReplayGainMode;
+pub use loudness::{scan_album, scan_track, AlbumLoudness, Loudness, LoudnessResult};
 pub use metadata_parser::*;
-pub use player::get_file_samples;
+pub use mixer::CrossfadeCurve;
+pub use output::OutputBackend;
 pub use player::get_live_sample;
+pub use player::play_queue as playQueue;
 pub use player::play_track as playTrack;
+pub use player::PlaybackOptions;
+pub use player::Player;
+pub use player::{get_file_range_samples, get_file_samples};
+pub use resampler::ResampleQuality;
+pub use sample_stream::{
+    get_file_channels, get_file_downmix, Downmix, SampleBlock, SampleLayout, SampleStream,
+};
+pub use wav_export::{export_wav, WavBitDepth, WavExportOptions};
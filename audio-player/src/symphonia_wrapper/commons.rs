@@ -1,39 +1,93 @@
 use std::{fs::File, path::Path};
-use symphonia::core::errors::Result;
+use symphonia::core::codecs::{CodecParameters, Decoder, DecoderOptions};
+use symphonia::core::errors::Error;
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::{Hint, ProbeResult};
 
+use log::warn;
+
+use crate::audio_error::{AudioError, AudioResult};
+use crate::audio_format;
+
 /// get Symphonia probe for audio file exploitation
 ///
-/// # Panics
-/// will panic if path point to an unrecognized symphonia audio file, or if file doesn't have extension at all
+/// # Errors
+/// NoExtension if the content isn't sniffed and the file has no UTF-8 extension,
+/// UnsupportedFormat if symphonia doesn't recognize it
+///
+pub fn get_probe(music_path: &Path) -> AudioResult<ProbeResult> {
+    get_probe_with_options(music_path, &Default::default())
+}
+
+/// get Symphonia probe for gapless playback, encoder delay & padding are trimmed from decoded audio
+///
+/// # Errors
+/// same as get_probe
+///
+pub fn get_gapless_probe(music_path: &Path) -> AudioResult<ProbeResult> {
+    let fmt_opts = FormatOptions {
+        enable_gapless: true,
+        ..Default::default()
+    };
+    get_probe_with_options(music_path, &fmt_opts)
+}
+
+/// Creates a decoder for a track
+///
+/// # Errors
+/// UnsupportedCodec if symphonia has no decoder for it
 ///
-pub fn get_probe(music_path: &Path) -> Result<ProbeResult> {
+pub fn make_decoder(codec_params: &CodecParameters) -> AudioResult<Box<dyn Decoder>> {
+    symphonia::default::get_codecs()
+        .make(codec_params, &DecoderOptions::default())
+        .map_err(|err| match err {
+            Error::Unsupported(msg) => AudioError::UnsupportedCodec(msg.to_string()),
+            err => err.into(),
+        })
+}
+
+/// "end of stream" is currently the only way a format reader can indicate the media is complete
+pub fn is_end_of_stream(err: &Error) -> bool {
+    matches!(err, Error::IoError(err)
+        if err.kind() == std::io::ErrorKind::UnexpectedEof && err.to_string() == "end of stream")
+}
+
+fn get_probe_with_options(music_path: &Path, fmt_opts: &FormatOptions) -> AudioResult<ProbeResult> {
     // Create a hint to help the format registry guess what format reader is appropriate.
+    // Content is trusted over the extension, which may be missing or wrong.
+    let detection = audio_format::detect_format(music_path)?;
+    if detection.is_mismatch() {
+        warn!(
+            "{:?} extension doesn't match its {} content",
+            music_path,
+            detection.content.map_or("", |content| content.extension())
+        );
+    }
     let mut hint = Hint::new();
-    if let Some(ext) = music_path.extension() {
-        if let Some(ext_str) = ext.to_str() {
-            hint.with_extension(ext_str);
-            //  println!("hint is {:?}", hint);
-        } else {
-            // TODO add to a errorlog
-            panic!("invalid format {:?}", ext);
+    let ext_str = match detection.format() {
+        Some(format) if !format.is_supported() => {
+            return Err(AudioError::UnsupportedFormat(format!(
+                "{} decoding isn't built in, see audio-player cargo features",
+                format
+            )))
         }
-    } else {
-        // TODO add to a errorlog
-        panic!("no file extension for {:?}", music_path);
-    }
+        Some(format) => format.extension(),
+        None => music_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or(AudioError::NoExtension)?,
+    };
+    hint.with_extension(ext_str);
     let source = Box::new(File::open(music_path)?);
 
     // Create the media source stream using the boxed media source from above.
     let mss = MediaSourceStream::new(source, Default::default());
 
-    // Use the default options for metadata and format readers.
+    // Use the default options for metadata.
     let meta_opts: MetadataOptions = Default::default();
-    let fmt_opts: FormatOptions = Default::default();
 
     // Probe the media source stream for metadata and get the format reader.
-    symphonia::default::get_probe().format(&hint, mss, &fmt_opts, &meta_opts)
+    Ok(symphonia::default::get_probe().format(&hint, mss, fmt_opts, &meta_opts)?)
 }
@@ -0,0 +1,264 @@
+//!
+//! Structured description of an audio file: tracks, tags, visuals, cues and cue sheet tracks.
+//! Serialized as JSON for tooling, printed in a human layout by metadata_parser.
+//!
+
+use log::{info, warn};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use symphonia::core::formats::{Cue, Track};
+use symphonia::core::meta::{ColorMode, MetadataRevision, Tag, Visual};
+use symphonia::core::probe::ProbeResult;
+use symphonia::core::units::TimeBase;
+
+use crate::audio_format::{self, FormatDetection};
+use crate::cue_sheet::{self, CueTrack};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub tracks: Vec<TrackReport>,
+    /// Container format tags if any, tags found while probing otherwise
+    pub tags: Vec<TagReport>,
+    pub visuals: Vec<VisualReport>,
+    pub cues: Vec<CueReport>,
+    /// Virtual tracks read from an external CUE sheet
+    pub cue_tracks: Vec<CueTrack>,
+    /// Formats named by the extension and found in the content
+    pub format: Option<FormatDetection>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TrackReport {
+    /// i.e "0x1000"
+    pub codec_id: String,
+    /// Unknown codecs have no name
+    pub codec_short_name: Option<String>,
+    pub codec_long_name: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub start_ts: u64,
+    /// Start time in seconds, if the time base is known
+    pub start_time: Option<f64>,
+    pub n_frames: Option<u64>,
+    /// Duration in seconds, if the time base is known
+    pub duration: Option<f64>,
+    /// i.e "1/44100"
+    pub time_base: Option<String>,
+    pub encoder_delay: Option<u32>,
+    pub encoder_padding: Option<u32>,
+    pub sample_format: Option<String>,
+    pub bits_per_sample: Option<u32>,
+    pub channels: Option<usize>,
+    pub channel_map: Option<String>,
+    pub channel_layout: Option<String>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TagReport {
+    /// Standard key name, if the container key is a known one
+    pub std_key: Option<String>,
+    pub key: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VisualReport {
+    pub usage: Option<String>,
+    pub media_type: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub bits_per_pixel: Option<u32>,
+    /// Colors count of indexed visuals
+    pub palette: Option<u32>,
+    /// Size in bytes
+    pub size: usize,
+    pub tags: Vec<TagReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CueReport {
+    pub index: u32,
+    pub start_ts: u64,
+    pub tags: Vec<TagReport>,
+    pub points: Vec<CuePointReport>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CuePointReport {
+    pub start_offset_ts: u64,
+    pub tags: Vec<TagReport>,
+}
+
+impl FileReport {
+    pub fn new(path: &Path, probed: &mut ProbeResult) -> FileReport {
+        let tracks = probed
+            .format
+            .tracks()
+            .iter()
+            .map(TrackReport::from)
+            .collect();
+        let cues = probed.format.cues().iter().map(CueReport::from).collect();
+
+        // Prefer metadata that's provided in the container format, over other tags found during
+        // the probe operation.
+        let (tags, visuals) = if let Some(metadata_rev) = probed.format.metadata().current() {
+            // Warn that certain tags are preferred.
+            if probed.metadata.get().as_ref().is_some() {
+                info!("tags that are part of the container format are preferentially reported.");
+                info!("not reporting additional tags that were found while probing.");
+            }
+            get_revision_reports(metadata_rev)
+        } else if let Some(metadata_rev) = probed.metadata.get().as_ref().and_then(|m| m.current())
+        {
+            get_revision_reports(metadata_rev)
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        // A broken sheet doesn't prevent reporting the file itself.
+        let cue_tracks = cue_sheet::find_cue_tracks(path).unwrap_or_else(|err| {
+            warn!("cue sheet error: {}", err);
+            Vec::new()
+        });
+
+        FileReport {
+            path: path.to_path_buf(),
+            tracks,
+            tags,
+            visuals,
+            cues,
+            cue_tracks,
+            format: audio_format::detect_format(path).ok(),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+/// Tags, standard keys first, and visuals of a metadata revision
+pub fn get_revision_reports(rev: &MetadataRevision) -> (Vec<TagReport>, Vec<VisualReport>) {
+    let known = rev.tags().iter().filter(|tag| tag.is_known());
+    let unknown = rev.tags().iter().filter(|tag| !tag.is_known());
+    (
+        known.chain(unknown).map(TagReport::from).collect(),
+        rev.visuals().iter().map(VisualReport::from).collect(),
+    )
+}
+
+impl From<&Track> for TrackReport {
+    fn from(track: &Track) -> TrackReport {
+        let params = &track.codec_params;
+        let codec = symphonia::default::get_codecs().get_codec(params.codec);
+        let seconds = |ts: u64| params.time_base.map(|tb| get_seconds(ts, tb));
+        TrackReport {
+            codec_id: params.codec.to_string(),
+            codec_short_name: codec.map(|codec| codec.short_name.to_string()),
+            codec_long_name: codec.map(|codec| codec.long_name.to_string()),
+            sample_rate: params.sample_rate,
+            start_ts: params.start_ts,
+            start_time: seconds(params.start_ts),
+            n_frames: params.n_frames,
+            duration: params.n_frames.and_then(seconds),
+            time_base: params.time_base.map(|tb| tb.to_string()),
+            encoder_delay: params.delay,
+            encoder_padding: params.padding,
+            sample_format: params.sample_format.map(|format| format!("{:?}", format)),
+            bits_per_sample: params.bits_per_sample,
+            channels: params.channels.map(|channels| channels.count()),
+            channel_map: params.channels.map(|channels| channels.to_string()),
+            channel_layout: params.channel_layout.map(|layout| format!("{:?}", layout)),
+            language: track.language.clone(),
+        }
+    }
+}
+
+impl From<&Tag> for TagReport {
+    fn from(tag: &Tag) -> TagReport {
+        TagReport {
+            std_key: tag.std_key.map(|std_key| format!("{:?}", std_key)),
+            key: tag.key.clone(),
+            value: tag.value.to_string(),
+        }
+    }
+}
+
+impl From<&Visual> for VisualReport {
+    fn from(visual: &Visual) -> VisualReport {
+        VisualReport {
+            usage: visual.usage.map(|usage| format!("{:?}", usage)),
+            media_type: visual.media_type.clone(),
+            width: visual.dimensions.map(|dimensions| dimensions.width),
+            height: visual.dimensions.map(|dimensions| dimensions.height),
+            bits_per_pixel: visual.bits_per_pixel.map(|bpp| bpp.get()),
+            palette: match visual.color_mode {
+                Some(ColorMode::Indexed(colors)) => Some(colors.get()),
+                _ => None,
+            },
+            size: visual.data.len(),
+            tags: visual.tags.iter().map(TagReport::from).collect(),
+        }
+    }
+}
+
+impl From<&Cue> for CueReport {
+    fn from(cue: &Cue) -> CueReport {
+        CueReport {
+            index: cue.index,
+            start_ts: cue.start_ts,
+            tags: cue.tags.iter().map(TagReport::from).collect(),
+            points: cue
+                .points
+                .iter()
+                .map(|point| CuePointReport {
+                    start_offset_ts: point.start_offset_ts,
+                    tags: point.tags.iter().map(TagReport::from).collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+fn get_seconds(ts: u64, tb: TimeBase) -> f64 {
+    let time = tb.calc_time(ts);
+    time.seconds as f64 + time.frac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::meta::{MetadataBuilder, StandardTagKey, Value};
+
+    #[test]
+    fn it_serializes_tags_known_first() {
+        let mut builder = MetadataBuilder::new();
+        builder
+            .add_tag(Tag::new(None, "TXXX:MOOD", Value::from("calm")))
+            .add_tag(Tag::new(
+                Some(StandardTagKey::Artist),
+                "TPE1",
+                Value::from("Boards of Canada"),
+            ));
+        let (tags, visuals) = get_revision_reports(&builder.metadata());
+        let report = FileReport {
+            path: PathBuf::from("track.mp3"),
+            tracks: Vec::new(),
+            tags,
+            visuals,
+            cues: Vec::new(),
+            cue_tracks: Vec::new(),
+            format: None,
+        };
+
+        assert_eq!(
+            report.to_json().unwrap(),
+            concat!(
+                r#"{"path":"track.mp3","tracks":[],"tags":["#,
+                r#"{"std_key":"Artist","key":"TPE1","value":"Boards of Canada"},"#,
+                r#"{"std_key":null,"key":"TXXX:MOOD","value":"calm"}],"#,
+                r#""visuals":[],"cues":[],"cue_tracks":[],"format":null}"#
+            )
+        );
+    }
+}
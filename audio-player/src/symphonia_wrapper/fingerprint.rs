@@ -0,0 +1,482 @@
+//!
+//! Acoustic fingerprint of the first seconds of a file, to identify a same recording across encodes
+//! and bitrates. It follows Chromaprint default algorithm: chroma features of 11025 Hz mono frames,
+//! turned into 32 bits values by 16 rectangle filters. Resampling is done by rubato instead of
+//! Chromaprint resampler, values are close to `fpcalc -raw` ones but not bit exact.
+//!
+
+use std::path::Path;
+use std::time::Duration;
+
+use realfft::RealFftPlanner;
+use symphonia::core::audio::{AudioBufferRef, Channels, Signal, SignalSpec};
+
+use super::resampler::{ResampleQuality, Resampler};
+use super::sample_stream::{Downmix, SampleLayout, SampleStream};
+use crate::audio_error::{AudioError, AudioResult};
+
+const SAMPLE_RATE: u32 = 11025;
+const FRAME_SIZE: usize = 4096;
+/// Frames overlap by two thirds
+const FRAME_HOP: usize = FRAME_SIZE / 3;
+const MIN_FREQ: f64 = 28.0;
+const MAX_FREQ: f64 = 3520.0;
+const BANDS: usize = 12;
+/// Smoothing of chroma features over time
+const CHROMA_FILTER: [f64; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+/// Chroma features with a lower norm are silence
+const NORM_THRESHOLD: f64 = 0.01;
+/// Chromaprint id of its default algorithm, first byte of encoded fingerprints
+const ALGORITHM: u8 = 1;
+/// Frames handed to the resampler at once
+const RESAMPLE_CHUNK: usize = 1024;
+/// Time shift looked for when comparing fingerprints, in values i.e ~10s
+const MAX_OFFSET: usize = 80;
+
+/// Rectangle filter on chroma features over time, its value quantized on 2 bits
+struct Classifier {
+    kind: u8,
+    /// First chroma band
+    y: usize,
+    /// Chroma bands count
+    height: usize,
+    /// Frames count
+    width: usize,
+    thresholds: [f64; 3],
+}
+
+const fn classifier(
+    kind: u8,
+    y: usize,
+    height: usize,
+    width: usize,
+    thresholds: [f64; 3],
+) -> Classifier {
+    Classifier {
+        kind,
+        y,
+        height,
+        width,
+        thresholds,
+    }
+}
+
+/// Chromaprint default algorithm ones, each one giving 2 bits of a value
+const CLASSIFIERS: [Classifier; 16] = [
+    classifier(0, 4, 3, 15, [1.98215, 2.35817, 2.63523]),
+    classifier(4, 4, 6, 15, [-1.03809, -0.651211, -0.282167]),
+    classifier(1, 0, 4, 16, [-0.298702, 0.119262, 0.558497]),
+    classifier(3, 8, 2, 12, [-0.105439, 0.0153946, 0.135898]),
+    classifier(3, 4, 4, 8, [-0.142891, 0.0258736, 0.200632]),
+    classifier(4, 0, 3, 5, [-0.826319, -0.590612, -0.368214]),
+    classifier(1, 2, 2, 9, [-0.557409, -0.233035, 0.0534525]),
+    classifier(2, 7, 3, 4, [-0.0646826, 0.00620476, 0.0784847]),
+    classifier(2, 6, 2, 16, [-0.192387, -0.029699, 0.215855]),
+    classifier(2, 1, 3, 2, [-0.0397818, -0.00568076, 0.0292026]),
+    classifier(5, 10, 1, 15, [-0.53823, -0.369934, -0.190235]),
+    classifier(3, 6, 2, 10, [-0.124877, 0.0296483, 0.139239]),
+    classifier(2, 1, 1, 14, [-0.101475, 0.0225617, 0.231971]),
+    classifier(3, 5, 6, 4, [-0.0799915, -0.00729616, 0.063262]),
+    classifier(1, 9, 2, 12, [-0.272556, 0.019424, 0.302559]),
+    classifier(3, 4, 2, 14, [-0.164292, -0.0321188, 0.08463]),
+];
+
+/// Widest classifier, frames needed for one value
+const MAX_WIDTH: usize = 16;
+
+impl Classifier {
+    /// Value of the filter on frames starting at `x`, quantized as a gray code
+    fn classify(&self, image: &IntegralImage, x: usize) -> u32 {
+        let (w, h) = (self.width, self.height);
+        let area = |x1: usize, y1: usize, x2: usize, y2: usize| {
+            image.area(x + x1, self.y + y1, x + x2, self.y + y2)
+        };
+        let (a, b) = match self.kind {
+            0 => (area(0, 0, w, h), 0.0),
+            // Lower bands against upper ones
+            1 => (area(0, h / 2, w, h), area(0, 0, w, h / 2)),
+            // Later frames against earlier ones
+            2 => (area(w / 2, 0, w, h), area(0, 0, w / 2, h)),
+            // Diagonal quarters against the other ones
+            3 => (
+                area(0, h / 2, w / 2, h) + area(w / 2, 0, w, h / 2),
+                area(0, 0, w / 2, h / 2) + area(w / 2, h / 2, w, h),
+            ),
+            // Middle bands against outer ones
+            4 => (
+                area(0, h / 3, w, 2 * h / 3),
+                area(0, 0, w, h / 3) + area(0, 2 * h / 3, w, h),
+            ),
+            // Middle frames against outer ones
+            _ => (
+                area(w / 3, 0, 2 * w / 3, h),
+                area(0, 0, w / 3, h) + area(2 * w / 3, 0, w, h),
+            ),
+        };
+        let value = ((1.0 + a) / (1.0 + b)).ln();
+        let [t0, t1, t2] = self.thresholds;
+        match value {
+            value if value < t0 => 0,
+            value if value < t1 => 1,
+            value if value < t2 => 3,
+            _ => 2,
+        }
+    }
+}
+
+/// Sums of chroma features from the first frame & band, to get any rectangle sum at once
+struct IntegralImage {
+    sums: Vec<[f64; BANDS + 1]>,
+}
+
+impl IntegralImage {
+    fn new(features: &[[f64; BANDS]]) -> IntegralImage {
+        let mut sums = vec![[0.0; BANDS + 1]; features.len() + 1];
+        for (x, row) in features.iter().enumerate() {
+            for (y, feature) in row.iter().enumerate() {
+                sums[x + 1][y + 1] = feature + sums[x][y + 1] + sums[x + 1][y] - sums[x][y];
+            }
+        }
+        IntegralImage { sums }
+    }
+
+    /// Sum of frames x1..x2 & bands y1..y2
+    fn area(&self, x1: usize, y1: usize, x2: usize, y2: usize) -> f64 {
+        self.sums[x2][y2] - self.sums[x1][y2] - self.sums[x2][y1] + self.sums[x1][y1]
+    }
+}
+
+/// Acoustic fingerprint, comparable across encodes of a same recording with similarity
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// Decoded audio duration, at most the requested one
+    pub duration: Duration,
+    /// One value per ~0.124s of audio
+    pub values: Vec<u32>,
+}
+
+impl Fingerprint {
+    /// Chromaprint compressed form, as `fpcalc` prints it: XOR of successive values
+    /// stored as set bit deltas, base64 url safe encoded without padding
+    pub fn encode(&self) -> String {
+        let mut normal = Vec::new();
+        let mut exceptional = Vec::new();
+        let mut previous = 0;
+        for value in &self.values {
+            let mut bits = value ^ previous;
+            previous = *value;
+            let (mut bit, mut last_bit) = (1, 0);
+            while bits != 0 {
+                if bits & 1 != 0 {
+                    let delta = bit - last_bit;
+                    normal.push(delta.min(7));
+                    if delta >= 7 {
+                        exceptional.push(delta - 7);
+                    }
+                    last_bit = bit;
+                }
+                bits >>= 1;
+                bit += 1;
+            }
+            normal.push(0);
+        }
+        let len = self.values.len();
+        let mut bytes = vec![ALGORITHM, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+        pack_bits(&mut bytes, &normal, 3);
+        pack_bits(&mut bytes, &exceptional, 5);
+        encode_base64(&bytes)
+    }
+
+    /// Share of equal bits, from 0 to 1, at the best time shift between both fingerprints.
+    /// Encodes of a same recording score above ~0.85, unrelated ones around 0.5
+    ///
+    /// # Returns
+    /// 1 if both fingerprints are empty, None if only one is
+    pub fn similarity(&self, other: &Fingerprint) -> Option<f64> {
+        let (a, b) = (&self.values, &other.values);
+        match (a.is_empty(), b.is_empty()) {
+            (true, true) => return Some(1.0),
+            (true, false) | (false, true) => return None,
+            (false, false) => (),
+        }
+        // At least half of the shortest fingerprint must overlap.
+        let min_overlap = (a.len().min(b.len()) / 2).max(1);
+        let offsets = (0..=MAX_OFFSET)
+            .map(|offset| (offset, 0))
+            .chain((1..=MAX_OFFSET).map(|offset| (0, offset)));
+        let mut best: f64 = 0.0;
+        for (a_start, b_start) in offsets {
+            let overlap = a
+                .len()
+                .saturating_sub(a_start)
+                .min(b.len().saturating_sub(b_start));
+            if overlap < min_overlap {
+                continue;
+            }
+            let errors: u32 = a[a_start..a_start + overlap]
+                .iter()
+                .zip(&b[b_start..])
+                .map(|(a, b)| (a ^ b).count_ones())
+                .sum();
+            best = best.max(1.0 - f64::from(errors) / (overlap * 32) as f64);
+        }
+        Some(best)
+    }
+}
+
+/// Fingerprints the first `length` of an audio file, mixed into mono
+///
+/// # Errors
+/// same as SampleStream::open_range, Decode if the audio is too short to get a single value,
+/// about 3 seconds
+pub fn get_fingerprint(audio_path: &Path, length: Duration) -> AudioResult<Fingerprint> {
+    let stream =
+        SampleStream::<f32>::open_range(audio_path, SampleLayout::Planar, None, Some(length))?;
+    let rate = stream
+        .spec()
+        .ok_or_else(|| AudioError::UnsupportedFormat("unknown sample rate".to_string()))?
+        .rate;
+    let mut resampler = (rate != SAMPLE_RATE).then(|| {
+        let spec = SignalSpec::new(rate, Channels::FRONT_CENTRE);
+        let chunk = RESAMPLE_CHUNK as u64;
+        Resampler::new(spec, SAMPLE_RATE, chunk, ResampleQuality::Balanced)
+    });
+
+    let mut samples = Vec::new();
+    let mut decoded = 0;
+    for block in stream {
+        let mono = block?.downmix(Downmix::Average);
+        decoded += mono.len() as u64;
+        let Some(resampler) = resampler.as_mut() else {
+            samples.extend(mono);
+            continue;
+        };
+        for chunk in mono.chunks(RESAMPLE_CHUNK) {
+            if let Some(AudioBufferRef::F32(buf)) = resampler.resample_planes(&[chunk.to_vec()]) {
+                samples.extend_from_slice(buf.chan(0));
+            }
+        }
+    }
+    if let Some(AudioBufferRef::F32(buf)) = resampler.as_mut().and_then(Resampler::flush) {
+        samples.extend_from_slice(buf.chan(0));
+    }
+    let values = compute_values(&samples);
+    if values.is_empty() {
+        return Err(AudioError::Decode(
+            "audio too short to fingerprint".to_string(),
+        ));
+    }
+    Ok(Fingerprint {
+        duration: Duration::from_secs_f64(decoded as f64 / f64::from(rate)),
+        values,
+    })
+}
+
+/// Fingerprint values of 11025 Hz mono samples
+fn compute_values(samples: &[f32]) -> Vec<u32> {
+    let chroma = compute_chroma(samples);
+    // Chromaprint filter starts once its buffer is full, first frame is dropped.
+    let features: Vec<[f64; BANDS]> = chroma
+        .windows(CHROMA_FILTER.len())
+        .skip(1)
+        .map(|frames| {
+            let mut filtered = [0.0; BANDS];
+            for (frame, coefficient) in frames.iter().zip(CHROMA_FILTER) {
+                for (sum, feature) in filtered.iter_mut().zip(frame) {
+                    *sum += feature * coefficient;
+                }
+            }
+            let norm = filtered.iter().map(|f| f * f).sum::<f64>().sqrt();
+            filtered.map(|f| if norm < NORM_THRESHOLD { 0.0 } else { f / norm })
+        })
+        .collect();
+
+    let image = IntegralImage::new(&features);
+    let count = (features.len() + 1).saturating_sub(MAX_WIDTH);
+    (0..count)
+        .map(|x| {
+            CLASSIFIERS.iter().fold(0, |bits, classifier| {
+                (bits << 2) | classifier.classify(&image, x)
+            })
+        })
+        .collect()
+}
+
+/// Energy of each of the 12 semitones, octaves merged, for each frame
+fn compute_chroma(samples: &[f32]) -> Vec<[f64; BANDS]> {
+    let fft = RealFftPlanner::<f64>::new().plan_fft_forward(FRAME_SIZE);
+    let mut frame = fft.make_input_vec();
+    let mut spectrum = fft.make_output_vec();
+    let window: Vec<f64> = (0..FRAME_SIZE)
+        .map(|i| {
+            0.54 - 0.46 * (i as f64 * 2.0 * std::f64::consts::PI / (FRAME_SIZE - 1) as f64).cos()
+        })
+        .collect();
+    let index = |freq: f64| (FRAME_SIZE as f64 * freq / f64::from(SAMPLE_RATE)).round() as usize;
+    let (min_index, max_index) = (index(MIN_FREQ).max(1), index(MAX_FREQ).min(FRAME_SIZE / 2));
+    // Semitone of each FFT bin, A0 being the first one
+    let notes: Vec<usize> = (0..max_index)
+        .map(|i| {
+            let freq = i as f64 * f64::from(SAMPLE_RATE) / FRAME_SIZE as f64;
+            let octave = (freq / 27.5).log2();
+            (BANDS as f64 * (octave - octave.floor())) as usize
+        })
+        .collect();
+
+    let mut chroma = Vec::new();
+    let mut start = 0;
+    while start + FRAME_SIZE <= samples.len() {
+        for ((input, sample), weight) in frame.iter_mut().zip(&samples[start..]).zip(&window) {
+            *input = f64::from(*sample) * weight;
+        }
+        fft.process(&mut frame, &mut spectrum)
+            .expect("fft buffers are sized by the planner");
+        let mut features = [0.0; BANDS];
+        for i in min_index..max_index {
+            features[notes[i]] += spectrum[i].norm_sqr();
+        }
+        chroma.push(features);
+        start += FRAME_HOP;
+    }
+    chroma
+}
+
+/// Packs values of `bits` bits, least significant bits first
+fn pack_bits(bytes: &mut Vec<u8>, values: &[u32], bits: u32) {
+    let (mut buffer, mut filled) = (0u32, 0);
+    for value in values {
+        buffer |= value << filled;
+        filled += bits;
+        while filled >= 8 {
+            bytes.push(buffer as u8);
+            buffer >>= 8;
+            filled -= 8;
+        }
+    }
+    if filled > 0 {
+        bytes.push(buffer as u8);
+    }
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let mut encoded = String::with_capacity((bytes.len() * 4).div_ceil(3));
+    for chunk in bytes.chunks(3) {
+        let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (i, byte)| {
+            buffer | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..=chunk.len() {
+            encoded.push(ALPHABET[(buffer >> (18 - 6 * i) & 0x3F) as usize] as char);
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_path, write_ramp, write_wav};
+    use std::f32::consts::PI;
+
+    /// Stereo melody of 2 harmonics tones, one per half second, notes being semitones from A4
+    fn write_melody(name: &str, rate: u32, notes: &[i32]) -> std::path::PathBuf {
+        let path = temp_path(name);
+        let frames = notes.iter().enumerate().flat_map(|(idx, note)| {
+            let freq = 440.0 * 2f32.powf(*note as f32 / 12.0);
+            (0..rate / 2).map(move |frame| {
+                let t = (idx as u32 * rate / 2 + frame) as f32 / rate as f32;
+                let sample = 0.3 * (2.0 * PI * freq * t).sin() + 0.1 * (4.0 * PI * freq * t).sin();
+                [(sample * 32767.0) as i16; 2]
+            })
+        });
+        write_wav(&path, rate, frames);
+        path
+    }
+
+    #[test]
+    fn it_encodes_as_chromaprint() {
+        let fingerprint = Fingerprint {
+            duration: Duration::ZERO,
+            values: vec![1],
+        };
+        // Algorithm & length header, then a single bit delta
+        assert_eq!(fingerprint.encode(), "AQAAAQE");
+        let fingerprint = Fingerprint {
+            duration: Duration::ZERO,
+            values: vec![0x8000_0001, 0x8000_0001],
+        };
+        // Deltas 1 & 31, the second one exceptional, then an unchanged value
+        assert_eq!(fingerprint.encode(), "AQAAAjkAGA");
+    }
+
+    #[test]
+    fn it_compares_shifted_fingerprints() {
+        let values: Vec<u32> = (0..100u32).map(|i| i.wrapping_mul(0x9E37_79B9)).collect();
+        let a = Fingerprint {
+            duration: Duration::ZERO,
+            values: values.clone(),
+        };
+        let shifted = Fingerprint {
+            duration: Duration::ZERO,
+            values: values[10..].to_vec(),
+        };
+        assert_eq!(a.similarity(&shifted), Some(1.0));
+        assert_eq!(shifted.similarity(&a), Some(1.0));
+        let unrelated = Fingerprint {
+            duration: Duration::ZERO,
+            values: (0..100u32).map(|i| i.wrapping_mul(0x85EB_CA6B)).collect(),
+        };
+        let similarity = a.similarity(&unrelated).unwrap();
+        assert!((0.4..0.6).contains(&similarity));
+        let empty = Fingerprint {
+            duration: Duration::ZERO,
+            values: Vec::new(),
+        };
+        assert_eq!(empty.similarity(&empty), Some(1.0));
+        assert_eq!(a.similarity(&empty), None);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_rejects_too_short_audio() {
+        // One second, shorter than the fingerprint filters
+        let path = write_ramp("fingerprint-short.wav", 8000);
+
+        let fingerprint = get_fingerprint(&path, Duration::from_secs(120));
+        assert!(matches!(fingerprint, Err(AudioError::Decode(_))));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_matches_same_melody_across_rates() {
+        let melody = [
+            0, 3, 7, 12, 7, 3, 0, -5, -2, 2, 5, 9, 5, 2, -2, -5, 0, 4, 7, 11,
+        ];
+        let other = [
+            1, 10, 6, -3, 8, 2, 11, -6, 4, 9, -1, 6, 3, 10, -4, 8, 1, 5, -2, 7,
+        ];
+        let paths = [
+            write_melody("melody-44100.wav", 44100, &melody),
+            write_melody("melody-22050.wav", 22050, &melody),
+            write_melody("other-44100.wav", 44100, &other),
+        ];
+
+        let length = Duration::from_secs(120);
+        let original = get_fingerprint(&paths[0], length).unwrap();
+        let resampled = get_fingerprint(&paths[1], length).unwrap();
+        let unrelated = get_fingerprint(&paths[2], length).unwrap();
+        assert_eq!(original.duration, Duration::from_secs(10));
+        // 10 seconds, minus the frames needed by filters
+        assert_eq!(original.values.len(), 58);
+        assert!(original.similarity(&resampled).unwrap() > 0.9);
+        assert!(original.similarity(&unrelated).unwrap() < 0.8);
+        let start = get_fingerprint(&paths[2], Duration::from_secs(5)).unwrap();
+        assert_eq!(start.duration, Duration::from_secs(5));
+
+        for path in paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//!
+//! Software gain stage between the decoder and the audio output.
+//! Volume is set in dB, changes are ramped to avoid zipper noise, and a soft limiter
+//! keeps boosted samples under full scale.
+//! ReplayGain tags, when enabled, add a per track gain on top of the volume.
+//!
+
+use clap::ValueEnum;
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef};
+
+use crate::{AudioTag, MetadataValue};
+
+/// Time to reach a new volume, in seconds
+const RAMP_DURATION: f32 = 0.02;
+/// Level from which the soft limiter starts compressing, -1dBFS
+const LIMITER_THRESHOLD: f32 = 0.891;
+
+/// ReplayGain values applied while playing
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    /// Each track is normalized on its own
+    Track,
+    /// Album tracks keep their relative loudness, falls back to track values if missing
+    Album,
+}
+
+/// ReplayGain tags of a track, gains in dB, peaks linear
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ReplayGain {
+    pub track_gain: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+impl ReplayGain {
+    /// Tags to read, in from_tags order
+    pub const TAGS: [AudioTag; 4] = [
+        AudioTag::ReplayGainTrackGain,
+        AudioTag::ReplayGainTrackPeak,
+        AudioTag::ReplayGainAlbumGain,
+        AudioTag::ReplayGainAlbumPeak,
+    ];
+
+    /// # Arguments
+    /// * `values` - tags content, ordered as ReplayGain::TAGS
+    pub fn from_tags(values: &[Option<MetadataValue>]) -> ReplayGain {
+        let value = |idx: usize| {
+            values
+                .get(idx)
+                .and_then(|v| v.as_ref())
+                .and_then(MetadataValue::as_f64)
+                .map(|v| v as f32)
+        };
+        ReplayGain {
+            track_gain: value(0),
+            track_peak: value(1),
+            album_gain: value(2),
+            album_peak: value(3),
+        }
+    }
+
+    /// Gain to apply in dB, lowered so the peak doesn't clip
+    pub fn gain_db(&self, mode: ReplayGainMode) -> f32 {
+        let (gain, peak) = match mode {
+            ReplayGainMode::Off => return 0.0,
+            ReplayGainMode::Album if self.album_gain.is_some() => {
+                (self.album_gain, self.album_peak)
+            }
+            _ => (self.track_gain, self.track_peak),
+        };
+        let gain = gain.unwrap_or(0.0);
+        match peak {
+            Some(peak) if peak > 0.0 => gain.min(-20.0 * peak.log10()),
+            _ => gain,
+        }
+    }
+}
+
+pub struct GainStage {
+    /// Volume requested, in dB
+    volume_db: f32,
+    /// ReplayGain of the playing track, in dB
+    replay_gain_db: f32,
+    /// Linear gain requested, mute excluded
+    target: f32,
+    /// Linear gain applied on last frame
+    current: f32,
+    muted: bool,
+    /// Gain increment per frame, computed at the start of a ramp
+    ramp_step: Option<f32>,
+    /// Decoded buffer converted to f32, gain applied
+    buffer: Option<AudioBuffer<f32>>,
+    /// False until the first buffer went through the stage
+    started: bool,
+}
+
+impl GainStage {
+    pub fn new(volume_db: f32, muted: bool) -> GainStage {
+        let target = db_to_linear(volume_db);
+        GainStage {
+            volume_db,
+            replay_gain_db: 0.0,
+            target,
+            current: if muted { 0.0 } else { target },
+            muted,
+            ramp_step: None,
+            buffer: None,
+            started: false,
+        }
+    }
+
+    pub fn set_volume(&mut self, volume_db: f32) {
+        self.volume_db = volume_db;
+        self.update_target();
+    }
+
+    /// Sets the gain of the next track. Applied at once before playback starts, ramped otherwise.
+    pub fn set_replay_gain(&mut self, replay_gain_db: f32) {
+        self.replay_gain_db = replay_gain_db;
+        self.update_target();
+        if !self.started {
+            self.current = self.effective_target();
+        }
+    }
+
+    fn update_target(&mut self) {
+        self.target = db_to_linear(self.volume_db + self.replay_gain_db);
+        self.ramp_step = None;
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        self.ramp_step = None;
+    }
+
+    /// Applies gain on a decoded buffer. Unity gain leaves it untouched.
+    pub fn apply<'a>(&'a mut self, decoded: AudioBufferRef<'a>) -> AudioBufferRef<'a> {
+        self.started = true;
+        let target = self.effective_target();
+        if self.current == 1.0 && target == 1.0 {
+            return decoded;
+        }
+
+        let spec = *decoded.spec();
+        let duration = decoded.capacity() as u64;
+        if !self
+            .buffer
+            .as_ref()
+            .is_some_and(|buffer| *buffer.spec() == spec && buffer.capacity() as u64 >= duration)
+        {
+            self.buffer = Some(AudioBuffer::<f32>::new(duration, spec));
+        }
+        let buffer = self.buffer.as_mut().expect("gain buffer allocated above");
+        decoded.convert(buffer);
+
+        if self.current != target && self.ramp_step.is_none() {
+            self.ramp_step = Some((target - self.current) / (RAMP_DURATION * spec.rate as f32));
+        }
+
+        {
+            let mut planes_mut = buffer.planes_mut();
+            let planes = planes_mut.planes();
+            for frame in 0..planes.first().map_or(0, |plane| plane.len()) {
+                if let Some(step) = self.ramp_step {
+                    self.current += step;
+                    if (step > 0.0 && self.current >= target)
+                        || (step < 0.0 && self.current <= target)
+                    {
+                        self.current = target;
+                        self.ramp_step = None;
+                    }
+                }
+                for plane in planes.iter_mut() {
+                    let sample = plane[frame] * self.current;
+                    // Only a boost can push samples past full scale.
+                    plane[frame] = if self.current > 1.0 {
+                        soft_limit(sample)
+                    } else {
+                        sample
+                    };
+                }
+            }
+        }
+        buffer.as_audio_buffer_ref()
+    }
+
+    fn effective_target(&self) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.target
+        }
+    }
+}
+
+pub fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+/// Leaves samples under threshold untouched, smoothly compresses the ones above it under full scale
+fn soft_limit(sample: f32) -> f32 {
+    let magnitude = sample.abs();
+    if magnitude <= LIMITER_THRESHOLD {
+        return sample;
+    }
+    let headroom = 1.0 - LIMITER_THRESHOLD;
+    sample.signum()
+        * (LIMITER_THRESHOLD + headroom * ((magnitude - LIMITER_THRESHOLD) / headroom).tanh())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::audio::{Channels, Signal, SignalSpec};
+
+    const RATE: u32 = 44100;
+    const FRAMES: usize = 1152;
+
+    fn constant_buffer(value: f32) -> AudioBuffer<f32> {
+        let spec = SignalSpec::new(RATE, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let mut buffer = AudioBuffer::<f32>::new(FRAMES as u64, spec);
+        buffer.render_reserved(Some(FRAMES));
+        for plane in buffer.planes_mut().planes().iter_mut() {
+            plane.fill(value);
+        }
+        buffer
+    }
+
+    fn apply(gain: &mut GainStage, input: &AudioBuffer<f32>) -> Vec<f32> {
+        match gain.apply(input.as_audio_buffer_ref()) {
+            AudioBufferRef::F32(buffer) => buffer.chan(0).to_vec(),
+            _ => unreachable!("gain stage outputs f32 buffers"),
+        }
+    }
+
+    #[test]
+    fn it_leaves_unity_gain_untouched() {
+        let mut gain = GainStage::new(0.0, false);
+        let output = apply(&mut gain, &constant_buffer(0.3));
+        assert!(output.iter().all(|sample| *sample == 0.3));
+    }
+
+    #[test]
+    fn it_applies_db_volume() {
+        let mut gain = GainStage::new(-6.0, false);
+        let output = apply(&mut gain, &constant_buffer(0.5));
+        assert!(output.iter().all(|sample| (sample - 0.2506).abs() < 1e-3));
+    }
+
+    #[test]
+    fn it_ramps_on_mute() {
+        let mut gain = GainStage::new(0.0, false);
+        gain.set_muted(true);
+        let input = constant_buffer(0.8);
+        let mut output = apply(&mut gain, &input);
+        output.extend(apply(&mut gain, &input));
+
+        // no jump between frames, then silence once the ramp is over
+        assert!(output
+            .windows(2)
+            .all(|pair| (pair[0] - pair[1]).abs() < 0.01));
+        let ramp_frames = (RAMP_DURATION * RATE as f32) as usize;
+        assert!(output[ramp_frames + 1..]
+            .iter()
+            .all(|sample| *sample == 0.0));
+    }
+
+    #[test]
+    fn it_parses_replay_gain_tags() {
+        let tags = [
+            Some(MetadataValue::parse(&ReplayGain::TAGS[0], "-6.54 dB")),
+            Some(MetadataValue::parse(&ReplayGain::TAGS[1], "0.988553")),
+            None,
+            Some(MetadataValue::parse(&ReplayGain::TAGS[3], "invalid")),
+        ];
+        let replay_gain = ReplayGain::from_tags(&tags);
+        assert_eq!(replay_gain.track_gain, Some(-6.54));
+        assert_eq!(replay_gain.track_peak, Some(0.988553));
+        assert_eq!(replay_gain.album_gain, None);
+        assert_eq!(replay_gain.album_peak, None);
+        // album mode falls back on track values
+        assert_eq!(replay_gain.gain_db(ReplayGainMode::Album), -6.54);
+        assert_eq!(replay_gain.gain_db(ReplayGainMode::Off), 0.0);
+    }
+
+    #[test]
+    fn it_prevents_replay_gain_clipping() {
+        let replay_gain = ReplayGain {
+            track_gain: Some(3.0),
+            track_peak: Some(0.9),
+            album_gain: Some(-2.0),
+            album_peak: Some(0.9),
+        };
+        // 0.9 peak only allows a 0.915dB boost
+        assert!((replay_gain.gain_db(ReplayGainMode::Track) - 0.915).abs() < 1e-3);
+        assert_eq!(replay_gain.gain_db(ReplayGainMode::Album), -2.0);
+    }
+
+    #[test]
+    fn it_limits_boosted_samples_under_full_scale() {
+        let mut gain = GainStage::new(12.0, false);
+        let output = apply(&mut gain, &constant_buffer(0.9));
+        assert!(output.iter().all(|sample| *sample <= 1.0 && *sample > 0.9));
+    }
+}
@@ -0,0 +1,221 @@
+//!
+//! EBU R128 loudness scanner, computing integrated loudness, loudness range and true peak
+//! of tracks and albums, and the matching ReplayGain 2.0 values.
+//!
+
+use ebur128::{EbuR128, Mode};
+use std::path::{Path, PathBuf};
+
+use crate::audio_error::{AudioError, AudioResult};
+
+use super::gain::ReplayGain;
+use super::sample_stream::{SampleLayout, SampleStream};
+
+/// ReplayGain 2.0 reference level, in LUFS
+const REPLAY_GAIN_REFERENCE: f64 = -18.0;
+
+pub type LoudnessResult<T> = AudioResult<T>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loudness {
+    /// Integrated loudness, in LUFS
+    pub integrated: f64,
+    /// Loudness range, in LU
+    pub range: f64,
+    /// Highest true peak among channels, linear
+    pub true_peak: f64,
+}
+
+impl Loudness {
+    /// Gain bringing the audio to ReplayGain reference level, in dB
+    pub fn replay_gain_db(&self) -> f64 {
+        REPLAY_GAIN_REFERENCE - self.integrated
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlbumLoudness {
+    /// Tracks loudness, ordered as scanned
+    pub tracks: Vec<(PathBuf, Loudness)>,
+    /// Loudness of all the tracks played as one
+    pub album: Loudness,
+}
+
+impl AlbumLoudness {
+    /// ReplayGain tags values of each track, ordered as tracks
+    pub fn replay_gains(&self) -> Vec<ReplayGain> {
+        self.tracks
+            .iter()
+            .map(|(_, track)| ReplayGain {
+                track_gain: Some(track.replay_gain_db() as f32),
+                track_peak: Some(track.true_peak as f32),
+                album_gain: Some(self.album.replay_gain_db() as f32),
+                album_peak: Some(self.album.true_peak as f32),
+            })
+            .collect()
+    }
+}
+
+/// Scans a single audio file
+pub fn scan_track(audio_path: &Path) -> LoudnessResult<Loudness> {
+    get_loudness(&analyze(audio_path)?)
+}
+
+/// Scans audio files of an album, album loudness is computed as if tracks were played in a row
+///
+/// Tracks failing to decode are skipped, album loudness is then computed from the other ones.
+///
+/// # Returns
+/// album loudness, or an error if no track decoded, and the errors of skipped tracks by path
+pub fn scan_album(
+    audio_paths: &[PathBuf],
+) -> (LoudnessResult<AlbumLoudness>, Vec<(PathBuf, AudioError)>) {
+    let mut states = Vec::new();
+    let mut tracks = Vec::new();
+    let mut errors = Vec::new();
+    for audio_path in audio_paths {
+        match analyze(audio_path).and_then(|state| Ok((get_loudness(&state)?, state))) {
+            Ok((loudness, state)) => {
+                tracks.push((audio_path.to_path_buf(), loudness));
+                states.push(state);
+            }
+            Err(error) => errors.push((audio_path.to_path_buf(), error)),
+        }
+    }
+    if tracks.is_empty() {
+        let error = AudioError::Decode("no track of the album decoded".to_string());
+        return (Err(error), errors);
+    }
+
+    let album = EbuR128::loudness_global_multiple(states.iter()).and_then(|integrated| {
+        Ok(Loudness {
+            integrated,
+            range: EbuR128::loudness_range_multiple(states.iter())?,
+            true_peak: tracks
+                .iter()
+                .map(|(_, track)| track.true_peak)
+                .fold(0.0, f64::max),
+        })
+    });
+    (
+        album
+            .map(|album| AlbumLoudness { tracks, album })
+            .map_err(Into::into),
+        errors,
+    )
+}
+
+/// Feeds the whole decoded file to a loudness meter
+fn analyze(audio_path: &Path) -> LoudnessResult<EbuR128> {
+    let mut meter: Option<EbuR128> = None;
+
+    for block in SampleStream::open(audio_path, SampleLayout::Interleaved)? {
+        let block = block?;
+        let state = match meter.as_mut() {
+            Some(state) => state,
+            None => meter.insert(EbuR128::new(
+                block.channels as u32,
+                block.rate,
+                Mode::I | Mode::LRA | Mode::TRUE_PEAK,
+            )?),
+        };
+        state.add_frames_f32(&block.samples)?;
+    }
+    meter.ok_or_else(|| AudioError::Decode(format!("no audio decoded from {:?}", audio_path)))
+}
+
+fn get_loudness(state: &EbuR128) -> LoudnessResult<Loudness> {
+    let mut true_peak: f64 = 0.0;
+    for channel in 0..state.channels() {
+        true_peak = true_peak.max(state.true_peak(channel)?);
+    }
+    Ok(Loudness {
+        integrated: state.loudness_global()?,
+        range: state.loudness_range()?,
+        true_peak,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_path, write_wav};
+    use std::f32::consts::PI;
+
+    const RATE: u32 = 48000;
+
+    /// Writes a stereo 1kHz sine WAV file, made of (level in dBFS, whole seconds) segments
+    fn write_sine(path: &Path, segments: &[(f32, u32)]) {
+        let frames = segments.iter().flat_map(|(level, seconds)| {
+            let amplitude = 10f32.powf(level / 20.0);
+            (0..seconds * RATE).map(move |idx| {
+                let phase = 2.0 * PI * 1000.0 * (idx % RATE) as f32 / RATE as f32;
+                [amplitude * phase.sin(); 2]
+            })
+        });
+        write_wav(path, RATE, frames);
+    }
+
+    /// EBU Tech 3341 test case 1 & 2
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_measures_integrated_loudness() {
+        let loud = temp_path("loudness-23.wav");
+        let quiet = temp_path("loudness-33.wav");
+        write_sine(&loud, &[(-23.0, 20)]);
+        write_sine(&quiet, &[(-33.0, 20)]);
+
+        let (album, errors) = scan_album(&[loud.clone(), quiet.clone()]);
+        let album = album.unwrap();
+        assert!(errors.is_empty());
+        let (_, loud_track) = album.tracks[0];
+        let (_, quiet_track) = album.tracks[1];
+        assert!((loud_track.integrated + 23.0).abs() < 0.1);
+        assert!((quiet_track.integrated + 33.0).abs() < 0.1);
+        assert!((loud_track.replay_gain_db() - 5.0).abs() < 0.1);
+        // album loudness is the energy average of both tracks
+        assert!((album.album.integrated + 25.6).abs() < 0.1);
+
+        let _ = std::fs::remove_file(loud);
+        let _ = std::fs::remove_file(quiet);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_skips_undecodable_album_tracks() {
+        let loud = temp_path("loudness-skip-23.wav");
+        let broken = temp_path("loudness-skip-broken.wav");
+        let missing = temp_path("loudness-skip-missing.wav");
+        write_sine(&loud, &[(-23.0, 20)]);
+        std::fs::write(&broken, b"not a wav file").unwrap();
+
+        let (album, errors) = scan_album(&[loud.clone(), broken.clone(), missing.clone()]);
+        let album = album.unwrap();
+        assert_eq!(album.tracks.len(), 1);
+        assert_eq!(album.tracks[0].0, loud);
+        assert!((album.album.integrated + 23.0).abs() < 0.1);
+        let error_paths: Vec<_> = errors.into_iter().map(|(path, _)| path).collect();
+        assert_eq!(error_paths, vec![broken.clone(), missing.clone()]);
+
+        let (album, errors) = scan_album(&[broken.clone(), missing]);
+        assert!(album.is_err());
+        assert_eq!(errors.len(), 2);
+
+        let _ = std::fs::remove_file(loud);
+        let _ = std::fs::remove_file(broken);
+    }
+
+    /// EBU Tech 3342 test case 1
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_measures_loudness_range_and_true_peak() {
+        let path = temp_path("loudness-range.wav");
+        write_sine(&path, &[(-20.0, 20), (-30.0, 20)]);
+
+        let loudness = scan_track(&path).unwrap();
+        assert!((loudness.range - 10.0).abs() < 1.0);
+        assert!((20.0 * loudness.true_peak.log10() + 20.0).abs() < 0.1);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,75 +1,237 @@
 use symphonia::core::{
     codecs::CodecParameters,
-    formats::{Cue, Track},
-    meta::{ColorMode, StandardTagKey, Tag, Value, Visual},
+    formats::Track,
+    meta::{MetadataRevision, StandardTagKey, StandardVisualKey, Tag, Value, Visual},
     probe::ProbeResult,
-    units::TimeBase,
 };
 
-use crate::{audio_parser::TagsResult, audio_tags::AudioTag};
+use crate::{
+    audio_error::AudioResult,
+    audio_format::FormatDetection,
+    audio_parser::{AllTagsResult, ReportResult, TagsResult, VisualsResult},
+    audio_tags::AudioTag,
+    cue_sheet::CueTrack,
+    metadata_value::MetadataValue,
+};
 
 use super::commons;
+use super::file_report::{
+    get_revision_reports, CueReport, FileReport, TagReport, TrackReport, VisualReport,
+};
 
-use log::info;
-use std::error::Error;
+use std::collections::HashSet;
+use std::fs;
 use std::path::Path;
+use std::time::Duration;
 
-pub fn parse(music_path: &Path) {
-    let mut probed = match commons::get_probe(music_path) {
-        Ok(probe) => probe,
-        Err(err) => panic!("Unsupported format {}", err),
-    };
-    print_format(&mut probed);
+pub fn parse(music_path: &Path) -> AudioResult<()> {
+    print_format(&get_file_report(music_path)?);
+    Ok(())
+}
+
+/// Tracks, tags, visuals and cues of a file
+pub fn get_file_report(audio_path: &Path) -> ReportResult {
+    let mut probed = commons::get_probe(audio_path)?;
+    Ok(FileReport::new(audio_path, &mut probed))
 }
 
 /// # Returns tags content list ordered as input
-pub fn get_metadata_string(audio_path: &Path, target: &Vec<AudioTag>) -> TagsResult {
+pub fn get_metadata(audio_path: &Path, target: &[AudioTag]) -> TagsResult {
     let mut probed = commons::get_probe(audio_path)?;
+    Ok(get_probed_metadata(&mut probed, target))
+}
+
+/// Same as get_metadata, for an already probed file
+/// # Returns tags content list ordered as input
+pub fn get_probed_metadata(
+    probed: &mut ProbeResult,
+    target: &[AudioTag],
+) -> Vec<Option<MetadataValue>> {
     // Prefer metadata that's provided in the container format, over other tags found during the
     // probe operation.
 
-    let mut content_list: Vec<Option<String>> = Vec::new();
+    let mut content_list: Vec<Option<MetadataValue>> = Vec::new();
 
     for tag in target {
-        let tag_content: Option<String>;
+        let tag_content: Option<MetadataValue>;
         // try find target metadata in format :
-        if let Some(format_item) = get_tracks_string(probed.format.tracks(), tag) {
+        if let Some(format_item) = get_tracks_value(probed.format.tracks(), tag) {
             content_list.push(Some(format_item));
             continue;
         }
 
         // try finding target metadata in tags :
         if let Some(metadata_rev) = probed.format.metadata().current() {
-            tag_content = get_tag_string(metadata_rev.tags(), tag);
+            tag_content = get_tag_value(metadata_rev.tags(), tag);
         } else if let Some(metadata_rev) = probed.metadata.get().as_ref().and_then(|m| m.current())
         {
-            tag_content = get_tag_string(metadata_rev.tags(), tag);
+            tag_content = get_tag_value(metadata_rev.tags(), tag);
         } else {
             tag_content = None;
         }
         content_list.push(tag_content);
     }
 
-    Ok(content_list)
+    content_list
 }
 
-fn get_tag_string(tags: &[Tag], target: &AudioTag) -> Option<String> {
-    if !tags.is_empty() {
-        // Print tags with a standard tag key first, these are the most common tags.
-        for tag in tags.iter().filter(|tag| tag.is_known()) {
-            if let Some(matchin_result) = get_matching_tag(tag, target) {
-                return Some(matchin_result.to_string());
-            }
-        }
+/// # Returns raw tags content list ordered as input, keys are case insensitive
+pub fn get_raw_metadata(audio_path: &Path, keys: &[String]) -> TagsResult {
+    let mut probed = commons::get_probe(audio_path)?;
+    let tags = get_current_tags(&mut probed);
+    Ok(keys
+        .iter()
+        .map(|key| get_raw_tag_value(&tags, key))
+        .collect())
+}
 
-        // Print the remaining tags with keys truncated to 26 characters.
-        for tag in tags.iter().filter(|tag| !tag.is_known()) {
-            if let Some(matchin_result) = get_matching_tag(tag, target) {
-                return Some(matchin_result.to_string());
-            }
+/// # Returns every tag of the file as (raw key, value) pairs
+pub fn get_all_tags(audio_path: &Path) -> AllTagsResult {
+    let mut probed = commons::get_probe(audio_path)?;
+    Ok(get_current_tags(&mut probed)
+        .iter()
+        .map(|tag| {
+            (
+                tag.key.clone(),
+                MetadataValue::from_tag_value(&AudioTag::Unknown, &tag.value),
+            )
+        })
+        .collect())
+}
+
+/// Writes embedded visuals into a folder, named by usage i.e "front-cover.jpg"
+/// # Returns written files
+pub fn extract_visuals(audio_path: &Path, out_dir: &Path) -> VisualsResult {
+    let mut probed = commons::get_probe(audio_path)?;
+    let visuals = get_current_revision(&mut probed)
+        .map(|rev| rev.visuals().to_vec())
+        .unwrap_or_default();
+    if visuals.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    fs::create_dir_all(out_dir)?;
+    let mut names = HashSet::new();
+    let mut written = Vec::new();
+    for visual in visuals.iter() {
+        let visual_path = out_dir.join(get_visual_file_name(visual, &mut names));
+        fs::write(&visual_path, &visual.data)?;
+        written.push(visual_path);
+    }
+    Ok(written)
+}
+
+/// DEBUG : Prints a file embedded visuals
+pub fn print_file_visuals(audio_path: &Path) -> AudioResult<()> {
+    let mut probed = commons::get_probe(audio_path)?;
+    if let Some(rev) = get_current_revision(&mut probed) {
+        print_visuals(&get_revision_reports(&rev).1);
+    }
+    Ok(())
+}
+
+/// Metadata of the container format if any, metadata found while probing otherwise
+fn get_current_revision(probed: &mut ProbeResult) -> Option<MetadataRevision> {
+    if let Some(metadata_rev) = probed.format.metadata().current() {
+        Some(metadata_rev.clone())
+    } else {
+        probed
+            .metadata
+            .get()
+            .as_ref()
+            .and_then(|m| m.current())
+            .cloned()
+    }
+}
+
+fn get_current_tags(probed: &mut ProbeResult) -> Vec<Tag> {
+    get_current_revision(probed)
+        .map(|rev| rev.tags().to_vec())
+        .unwrap_or_default()
+}
+
+/// Visual usage in kebab case, with an extension matching its media type.
+/// Suffixed with a counter if the name is already taken, i.e "front-cover-2.jpg"
+fn get_visual_file_name(visual: &Visual, names: &mut HashSet<String>) -> String {
+    let usage = match visual.usage {
+        Some(StandardVisualKey::FileIcon) => "file-icon",
+        Some(StandardVisualKey::OtherIcon) => "other-icon",
+        Some(StandardVisualKey::FrontCover) => "front-cover",
+        Some(StandardVisualKey::BackCover) => "back-cover",
+        Some(StandardVisualKey::Leaflet) => "leaflet",
+        Some(StandardVisualKey::Media) => "media",
+        Some(StandardVisualKey::LeadArtistPerformerSoloist) => "lead-artist",
+        Some(StandardVisualKey::ArtistPerformer) => "artist",
+        Some(StandardVisualKey::Conductor) => "conductor",
+        Some(StandardVisualKey::BandOrchestra) => "band",
+        Some(StandardVisualKey::Composer) => "composer",
+        Some(StandardVisualKey::Lyricist) => "lyricist",
+        Some(StandardVisualKey::RecordingLocation) => "recording-location",
+        Some(StandardVisualKey::RecordingSession) => "recording-session",
+        Some(StandardVisualKey::Performance) => "performance",
+        Some(StandardVisualKey::ScreenCapture) => "screen-capture",
+        Some(StandardVisualKey::Illustration) => "illustration",
+        Some(StandardVisualKey::BandArtistLogo) => "band-logo",
+        Some(StandardVisualKey::PublisherStudioLogo) => "publisher-logo",
+        None => "other",
+    };
+    let extension = get_visual_extension(&visual.media_type);
+
+    let mut name = format!("{}.{}", usage, extension);
+    let mut counter = 1;
+    while !names.insert(name.clone()) {
+        counter += 1;
+        name = format!("{}-{}.{}", usage, counter, extension);
+    }
+    name
+}
+
+/// Accepts MIME types as well as ID3v2.2 image formats, i.e "image/jpeg" or "JPG"
+fn get_visual_extension(media_type: &str) -> &'static str {
+    let media_type = media_type.to_ascii_lowercase();
+    match media_type.trim_start_matches("image/") {
+        "jpeg" | "jpg" | "pjpeg" => "jpg",
+        "png" => "png",
+        "gif" => "gif",
+        "bmp" | "x-ms-bmp" => "bmp",
+        "webp" => "webp",
+        "tiff" => "tiff",
+        _ => "bin",
+    }
+}
+
+/// Matches a tag by its container key, i.e "TXXX:CATALOGNUMBER" or "ORIGINALYEAR"
+fn get_raw_tag_value(tags: &[Tag], key: &str) -> Option<MetadataValue> {
+    tags.iter()
+        .find(|tag| tag.key.eq_ignore_ascii_case(key))
+        .map(|tag| MetadataValue::from_tag_value(&AudioTag::Unknown, &tag.value))
+}
+
+fn get_tag_value(tags: &[Tag], target: &AudioTag) -> Option<MetadataValue> {
+    // Only tags with a standard tag key can match, others are reached by get_raw_metadata.
+    for tag in tags.iter().filter(|tag| tag.is_known()) {
+        if let Some(matchin_result) = get_matching_tag(tag, target) {
+            return Some(MetadataValue::from_tag_value(target, matchin_result));
         }
     }
-    None
+    // Totals are often stored along the number, i.e "3/12"
+    match target {
+        AudioTag::TrackTotal => get_total(tags, &AudioTag::TrackNumber),
+        AudioTag::DiscTotal => get_total(tags, &AudioTag::DiscNumber),
+        _ => None,
+    }
+}
+
+/// Reads the total part of a "number/total" tag
+fn get_total(tags: &[Tag], number_tag: &AudioTag) -> Option<MetadataValue> {
+    tags.iter()
+        .find_map(|tag| get_matching_tag(tag, number_tag))
+        .and_then(|value| match value {
+            Value::String(text) => text.split_once('/'),
+            _ => None,
+        })
+        .and_then(|(_, total)| total.trim().parse::<i64>().ok())
+        .map(MetadataValue::Integer)
 }
 
 fn get_matching_tag<'a>(tag: &'a Tag, target: &AudioTag) -> Option<&'a Value> {
@@ -87,41 +249,54 @@ fn get_stantard_tag_key(target: &AudioTag) -> Option<StandardTagKey> {
     match target {
         AudioTag::Artist => Some(StandardTagKey::Artist),
         AudioTag::Album => Some(StandardTagKey::Album),
+        AudioTag::AlbumArtist => Some(StandardTagKey::AlbumArtist),
+        AudioTag::Composer => Some(StandardTagKey::Composer),
+        AudioTag::Conductor => Some(StandardTagKey::Conductor),
         AudioTag::Bpm => Some(StandardTagKey::Bpm),
         AudioTag::Date => Some(StandardTagKey::Date),
         AudioTag::Genre => Some(StandardTagKey::Genre),
         AudioTag::Lyrics => Some(StandardTagKey::Lyrics),
         AudioTag::TrackNumber => Some(StandardTagKey::TrackNumber),
+        AudioTag::TrackTotal => Some(StandardTagKey::TrackTotal),
         AudioTag::TrackName => Some(StandardTagKey::TrackTitle),
+        AudioTag::DiscNumber => Some(StandardTagKey::DiscNumber),
+        AudioTag::DiscTotal => Some(StandardTagKey::DiscTotal),
+        AudioTag::Comment => Some(StandardTagKey::Comment),
+        AudioTag::Isrc => Some(StandardTagKey::IdentIsrc),
+        AudioTag::Label => Some(StandardTagKey::Label),
+        AudioTag::Compilation => Some(StandardTagKey::Compilation),
+        AudioTag::MusicBrainzRecordingId => Some(StandardTagKey::MusicBrainzRecordingId),
+        AudioTag::MusicBrainzReleaseId => Some(StandardTagKey::MusicBrainzAlbumId),
+        AudioTag::MusicBrainzArtistId => Some(StandardTagKey::MusicBrainzArtistId),
+        AudioTag::ReplayGainTrackGain => Some(StandardTagKey::ReplayGainTrackGain),
+        AudioTag::ReplayGainTrackPeak => Some(StandardTagKey::ReplayGainTrackPeak),
+        AudioTag::ReplayGainAlbumGain => Some(StandardTagKey::ReplayGainAlbumGain),
+        AudioTag::ReplayGainAlbumPeak => Some(StandardTagKey::ReplayGainAlbumPeak),
         _ => None,
     }
 }
 
-fn print_format(probed: &mut ProbeResult) {
-    print_tracks(probed.format.tracks());
-
-    // Prefer metadata that's provided in the container format, over other tags found during the
-    // probe operation.
-    if let Some(metadata_rev) = probed.format.metadata().current() {
-        print_tags(metadata_rev.tags());
-        print_visuals(metadata_rev.visuals());
-
-        // Warn that certain tags are preferred.
-        if probed.metadata.get().as_ref().is_some() {
-            info!("tags that are part of the container format are preferentially printed.");
-            info!("not printing additional tags that were found while probing.");
-        }
-    } else if let Some(metadata_rev) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
-        print_tags(metadata_rev.tags());
-        print_visuals(metadata_rev.visuals());
-    }
+fn print_format(report: &FileReport) {
+    print_format_detection(report.format.as_ref());
+    print_tracks(&report.tracks);
+    print_tags(&report.tags);
+    print_visuals(&report.visuals);
+    print_cues(&report.cues);
+    print_cue_tracks(&report.cue_tracks);
+    println!(":");
+    println!();
+}
 
-    print_cues(probed.format.cues());
+/// Prints a metadata revision read while playing, i.e a new chained stream
+pub fn print_update(rev: &MetadataRevision) {
+    let (tags, visuals) = get_revision_reports(rev);
+    print_tags(&tags);
+    print_visuals(&visuals);
     println!(":");
     println!();
 }
 
-fn get_tracks_string(tracks: &[Track], target: &AudioTag) -> Option<String> {
+fn get_tracks_value(tracks: &[Track], target: &AudioTag) -> Option<MetadataValue> {
     if tracks.is_empty() {
         return None;
     }
@@ -145,101 +320,112 @@ fn get_tracks_string(tracks: &[Track], target: &AudioTag) -> Option<String> {
 }
 
 /// Get total audio file frame count
-fn get_frame_number(params: &CodecParameters) -> Option<String> {
-    match params.n_frames {
-        Some(rate) => Some(rate.to_string()),
-        None => None,
-    }
+fn get_frame_number(params: &CodecParameters) -> Option<MetadataValue> {
+    params
+        .n_frames
+        .map(|frames| MetadataValue::Integer(frames as i64))
 }
 
-/// TODO returns numeric
-fn get_sample_rate(params: &CodecParameters) -> Option<String> {
-    match params.sample_rate {
-        Some(rate) => Some(rate.to_string()),
-        None => None,
-    }
+fn get_sample_rate(params: &CodecParameters) -> Option<MetadataValue> {
+    params
+        .sample_rate
+        .map(|rate| MetadataValue::Integer(i64::from(rate)))
+}
+
+fn get_channels(params: &CodecParameters) -> Option<MetadataValue> {
+    params
+        .channels
+        .map(|info| MetadataValue::Integer(info.count() as i64))
 }
-/// TODO returns numeric
-fn get_channels(params: &CodecParameters) -> Option<String> {
-    match params.channels {
-        Some(info) => Some(info.count().to_string()),
-        None => None,
+
+/// Duration if the time base is known, frame count otherwise
+fn get_duration(params: &CodecParameters) -> Option<MetadataValue> {
+    let n_frames = params.n_frames?;
+    match params.time_base {
+        Some(tb) => {
+            let time = tb.calc_time(n_frames);
+            Some(MetadataValue::Duration(
+                Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac),
+            ))
+        }
+        None => Some(MetadataValue::Integer(n_frames as i64)),
     }
 }
 
-fn get_duration(params: &CodecParameters) -> Option<String> {
-    if let Some(n_frames) = params.n_frames {
-        if let Some(tb) = params.time_base {
-            return Some(fmt_time(n_frames, tb));
-        } else {
-            return Some(n_frames.to_string());
+/// Only printed if the extension names another format than the content one
+fn print_format_detection(format: Option<&FormatDetection>) {
+    if let Some(format) = format.filter(|format| format.is_mismatch()) {
+        println!("|");
+        println!("| // Format //");
+        if let (Some(extension), Some(content)) = (format.extension, format.content) {
+            println!("|     Content:         {}", content);
+            println!("|     Extension:       {} (mismatch)", extension);
         }
     }
-    None
 }
 
-fn print_tracks(tracks: &[Track]) {
+fn print_tracks(tracks: &[TrackReport]) {
     if !tracks.is_empty() {
         println!("|");
         println!("| // Tracks //");
 
         for (idx, track) in tracks.iter().enumerate() {
-            let params = &track.codec_params;
-
             print!("|     [{:0>2}] Codec:           ", idx + 1);
 
-            if let Some(codec) = symphonia::default::get_codecs().get_codec(params.codec) {
-                println!("{} ({})", codec.long_name, codec.short_name);
+            if let (Some(long_name), Some(short_name)) =
+                (&track.codec_long_name, &track.codec_short_name)
+            {
+                println!("{} ({})", long_name, short_name);
             } else {
-                println!("Unknown (#{})", params.codec);
+                println!("Unknown (#{})", track.codec_id);
             }
 
-            if let Some(sample_rate) = params.sample_rate {
+            if let Some(sample_rate) = track.sample_rate {
                 println!("|          Sample Rate:     {}", sample_rate);
             }
-            if params.start_ts > 0 {
-                if let Some(tb) = params.time_base {
+            if track.start_ts > 0 {
+                if let Some(start_time) = track.start_time {
                     println!(
                         "|          Start Time:      {} ({})",
-                        fmt_time(params.start_ts, tb),
-                        params.start_ts
+                        fmt_seconds(start_time),
+                        track.start_ts
                     );
                 } else {
-                    println!("|          Start Time:      {}", params.start_ts);
+                    println!("|          Start Time:      {}", track.start_ts);
                 }
             }
-            if let Some(n_frames) = params.n_frames {
-                if let Some(tb) = params.time_base {
+            if let Some(n_frames) = track.n_frames {
+                if let Some(duration) = track.duration {
                     println!(
                         "|          Duration:        {} ({})",
-                        fmt_time(n_frames, tb),
+                        fmt_seconds(duration),
                         n_frames
                     );
                 } else {
                     println!("|          Frames:          {}", n_frames);
                 }
             }
-            if let Some(tb) = params.time_base {
+            if let Some(tb) = &track.time_base {
                 println!("|          Time Base:       {}", tb);
             }
-            if let Some(padding) = params.delay {
+            if let Some(padding) = track.encoder_delay {
                 println!("|          Encoder Delay:   {}", padding);
             }
-            if let Some(padding) = params.padding {
+            if let Some(padding) = track.encoder_padding {
                 println!("|          Encoder Padding: {}", padding);
             }
-            if let Some(sample_format) = params.sample_format {
-                println!("|          Sample Format:   {:?}", sample_format);
+            if let Some(sample_format) = &track.sample_format {
+                println!("|          Sample Format:   {}", sample_format);
             }
-            if let Some(bits_per_sample) = params.bits_per_sample {
+            if let Some(bits_per_sample) = track.bits_per_sample {
                 println!("|          Bits per Sample: {}", bits_per_sample);
             }
-            if let Some(channels) = params.channels {
-                println!("|          Channel(s):      {}", channels.count());
-                println!("|          Channel Map:     {}", channels);
+            if let (Some(channels), Some(channel_map)) = (track.channels, &track.channel_map) {
+                println!("|          Channel(s):      {}", channels);
+                println!("|          Channel Map:     {}", channel_map);
             }
-            if let Some(channel_layout) = params.channel_layout {
-                println!("|          Channel Layout:  {:?}", channel_layout);
+            if let Some(channel_layout) = &track.channel_layout {
+                println!("|          Channel Layout:  {}", channel_layout);
             }
             if let Some(language) = &track.language {
                 println!("|          Language:        {}", language);
@@ -248,7 +434,7 @@ fn print_tracks(tracks: &[Track]) {
     }
 }
 
-fn print_cues(cues: &[Cue]) {
+fn print_cues(cues: &[CueReport]) {
     if !cues.is_empty() {
         println!("|");
         println!("| // Cues //");
@@ -262,14 +448,7 @@ fn print_cues(cues: &[Cue]) {
                 println!("|          Tags:");
 
                 for (tidx, tag) in cue.tags.iter().enumerate() {
-                    if let Some(std_key) = tag.std_key {
-                        println!(
-                            "{}",
-                            print_tag_item(tidx + 1, &format!("{:?}", std_key), &tag.value, 21)
-                        );
-                    } else {
-                        println!("{}", print_tag_item(tidx + 1, &tag.key, &tag.value, 21));
-                    }
+                    println!("{}", print_tag_item(tidx + 1, tag, 21));
                 }
             }
 
@@ -297,57 +476,65 @@ fn print_cues(cues: &[Cue]) {
     }
 }
 
-fn print_tags(tags: &[Tag]) {
-    if !tags.is_empty() {
+fn print_cue_tracks(cue_tracks: &[CueTrack]) {
+    if !cue_tracks.is_empty() {
         println!("|");
-        println!("| // Tags //");
-
-        let mut idx = 1;
-
-        // Print tags with a standard tag key first, these are the most common tags.
-        for tag in tags.iter().filter(|tag| tag.is_known()) {
-            if let Some(std_key) = tag.std_key {
-                println!(
-                    "{}",
-                    print_tag_item(idx, &format!("{:?}", std_key), &tag.value, 4)
-                );
+        println!("| // Cue Sheet //");
+
+        for cue_track in cue_tracks {
+            println!(
+                "|     [{:0>2}] Title:      {}",
+                cue_track.number,
+                cue_track.title.as_deref().unwrap_or("")
+            );
+            if let Some(performer) = &cue_track.performer {
+                println!("|          Performer:  {}", performer);
+            }
+            println!(
+                "|          Start:      {}",
+                fmt_seconds(cue_track.start.as_secs_f64())
+            );
+            if let Some(end) = cue_track.end {
+                println!("|          End:        {}", fmt_seconds(end.as_secs_f64()));
             }
-            idx += 1;
         }
+    }
+}
 
-        // Print the remaining tags with keys truncated to 26 characters.
-        for tag in tags.iter().filter(|tag| !tag.is_known()) {
-            println!("{}", print_tag_item(idx, &tag.key, &tag.value, 4));
-            idx += 1;
+/// Tags with a standard tag key are expected first, these are the most common tags.
+fn print_tags(tags: &[TagReport]) {
+    if !tags.is_empty() {
+        println!("|");
+        println!("| // Tags //");
+
+        for (idx, tag) in tags.iter().enumerate() {
+            println!("{}", print_tag_item(idx + 1, tag, 4));
         }
     }
 }
 
-fn print_visuals(visuals: &[Visual]) {
+fn print_visuals(visuals: &[VisualReport]) {
     if !visuals.is_empty() {
         println!("|");
         println!("| // Visuals //");
 
         for (idx, visual) in visuals.iter().enumerate() {
-            if let Some(usage) = visual.usage {
-                println!("|     [{:0>2}] Usage:      {:?}", idx + 1, usage);
+            if let Some(usage) = &visual.usage {
+                println!("|     [{:0>2}] Usage:      {}", idx + 1, usage);
                 println!("|          Media Type: {}", visual.media_type);
             } else {
                 println!("|     [{:0>2}] Media Type: {}", idx + 1, visual.media_type);
             }
-            if let Some(dimensions) = visual.dimensions {
-                println!(
-                    "|          Dimensions: {} px x {} px",
-                    dimensions.width, dimensions.height
-                );
+            if let (Some(width), Some(height)) = (visual.width, visual.height) {
+                println!("|          Dimensions: {} px x {} px", width, height);
             }
             if let Some(bpp) = visual.bits_per_pixel {
                 println!("|          Bits/Pixel: {}", bpp);
             }
-            if let Some(ColorMode::Indexed(colors)) = visual.color_mode {
+            if let Some(colors) = visual.palette {
                 println!("|          Palette:    {} colors", colors);
             }
-            println!("|          Size:       {} bytes", visual.data.len());
+            println!("|          Size:       {} bytes", visual.size);
 
             // Print out tags similar to how regular tags are printed.
             if !visual.tags.is_empty() {
@@ -355,20 +542,15 @@ fn print_visuals(visuals: &[Visual]) {
             }
 
             for (tidx, tag) in visual.tags.iter().enumerate() {
-                if let Some(std_key) = tag.std_key {
-                    println!(
-                        "{}",
-                        print_tag_item(tidx + 1, &format!("{:?}", std_key), &tag.value, 21)
-                    );
-                } else {
-                    println!("{}", print_tag_item(tidx + 1, &tag.key, &tag.value, 21));
-                }
+                println!("{}", print_tag_item(tidx + 1, tag, 21));
             }
         }
     }
 }
 
-fn print_tag_item(idx: usize, key: &str, value: &Value, indent: usize) -> String {
+/// Standard key is printed if known, container key otherwise, truncated to 26 characters
+fn print_tag_item(idx: usize, tag: &TagReport, indent: usize) -> String {
+    let key = tag.std_key.as_ref().unwrap_or(&tag.key);
     let key_str = match key.len() {
         0..=28 => format!("| {:w$}[{:0>2}] {:<28} : ", "", idx, key, w = indent),
         _ => format!(
@@ -387,7 +569,7 @@ fn print_tag_item(idx: usize, key: &str, value: &Value, indent: usize) -> String
 
     out.push_str(&key_str);
 
-    for (wrapped, line) in value.to_string().lines().enumerate() {
+    for (wrapped, line) in tag.value.lines().enumerate() {
         if wrapped > 0 {
             out.push_str(&line_prefix);
         }
@@ -404,12 +586,99 @@ fn print_tag_item(idx: usize, key: &str, value: &Value, indent: usize) -> String
     out
 }
 
-fn fmt_time(ts: u64, tb: TimeBase) -> String {
-    let time = tb.calc_time(ts);
+fn fmt_seconds(seconds: f64) -> String {
+    let whole_seconds = seconds as u64;
 
-    let hours = time.seconds / (60 * 60);
-    let mins = (time.seconds % (60 * 60)) / 60;
-    let secs = f64::from((time.seconds % 60) as u32) + time.frac;
+    let hours = whole_seconds / (60 * 60);
+    let mins = (whole_seconds % (60 * 60)) / 60;
+    let secs = (whole_seconds % 60) as f64 + seconds.fract();
 
     format!("{}:{:0>2}:{:0>6.3}", hours, mins, secs)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tag(std_key: StandardTagKey, key: &str, value: &str) -> Tag {
+        Tag::new(Some(std_key), key, Value::from(value))
+    }
+
+    #[test]
+    fn it_reads_extended_tags() {
+        let tags = [
+            tag(
+                StandardTagKey::AlbumArtist,
+                "ALBUMARTIST",
+                "Various Artists",
+            ),
+            tag(StandardTagKey::DiscNumber, "TPOS", "2/3"),
+            tag(StandardTagKey::TrackNumber, "TRCK", "7"),
+            tag(StandardTagKey::Compilation, "TCMP", "1"),
+        ];
+        assert_eq!(
+            get_tag_value(&tags, &AudioTag::AlbumArtist),
+            Some(MetadataValue::Text("Various Artists".to_string()))
+        );
+        assert_eq!(
+            get_tag_value(&tags, &AudioTag::DiscNumber),
+            Some(MetadataValue::Integer(2))
+        );
+        // total read along the disc number
+        assert_eq!(
+            get_tag_value(&tags, &AudioTag::DiscTotal),
+            Some(MetadataValue::Integer(3))
+        );
+        assert_eq!(get_tag_value(&tags, &AudioTag::TrackTotal), None);
+        assert_eq!(
+            get_tag_value(&tags, &AudioTag::Compilation),
+            Some(MetadataValue::Integer(1))
+        );
+    }
+
+    #[test]
+    fn it_reads_raw_tags() {
+        let tags = [
+            Tag::new(None, "TXXX:CATALOGNUMBER", Value::from("WARPCD92")),
+            Tag::new(None, "ORIGINALYEAR", Value::from("1994")),
+        ];
+        assert_eq!(
+            get_raw_tag_value(&tags, "TXXX:CATALOGNUMBER"),
+            Some(MetadataValue::Text("WARPCD92".to_string()))
+        );
+        // Vorbis comment keys are case insensitive
+        assert_eq!(
+            get_raw_tag_value(&tags, "originalYear"),
+            Some(MetadataValue::Text("1994".to_string()))
+        );
+        assert_eq!(get_raw_tag_value(&tags, "LABEL"), None);
+    }
+
+    fn visual(media_type: &str, usage: Option<StandardVisualKey>) -> Visual {
+        Visual {
+            media_type: media_type.to_string(),
+            dimensions: None,
+            bits_per_pixel: None,
+            color_mode: None,
+            usage,
+            tags: Vec::new(),
+            data: Box::new([]),
+        }
+    }
+
+    #[test]
+    fn it_names_visual_files_by_usage() {
+        let mut names = HashSet::new();
+        let front = visual("image/jpeg", Some(StandardVisualKey::FrontCover));
+        let back = visual("PNG", Some(StandardVisualKey::BackCover));
+        let unknown = visual("application/octet-stream", None);
+
+        assert_eq!(get_visual_file_name(&front, &mut names), "front-cover.jpg");
+        assert_eq!(get_visual_file_name(&back, &mut names), "back-cover.png");
+        assert_eq!(get_visual_file_name(&unknown, &mut names), "other.bin");
+        assert_eq!(
+            get_visual_file_name(&front, &mut names),
+            "front-cover-2.jpg"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//!
+//! Crossfade mixer, blending the end of a track with the start of the next one.
+//!
+
+use clap::ValueEnum;
+use std::f32::consts::FRAC_PI_2;
+use symphonia::core::audio::AudioBuffer;
+
+/// Volume curves of the outgoing & incoming tracks during a crossfade
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum CrossfadeCurve {
+    /// Gains sum is constant, may sound quieter in the middle
+    #[default]
+    Linear,
+    /// Power sum is constant, keeps perceived loudness for uncorrelated tracks
+    EqualPower,
+}
+
+impl CrossfadeCurve {
+    /// # Arguments
+    /// * `progress` - crossfade progress, from 0 to 1
+    ///
+    /// # Returns
+    /// outgoing and incoming tracks gains
+    pub fn gains(&self, progress: f32) -> (f32, f32) {
+        let progress = progress.clamp(0.0, 1.0);
+        match self {
+            CrossfadeCurve::Linear => (1.0 - progress, progress),
+            CrossfadeCurve::EqualPower => {
+                ((progress * FRAC_PI_2).cos(), (progress * FRAC_PI_2).sin())
+            }
+        }
+    }
+}
+
+/// Mixes the incoming track frames into the outgoing track buffer
+///
+/// # Arguments
+/// * `outgoing` - ending track decoded buffer, mixed in place
+/// * `incoming` - next track planar frames, starting at the buffer first frame inside the
+///   crossfade. Missing frames are silence
+/// * `incoming_gain` - linear gain applied on incoming frames, i.e ReplayGain difference
+/// * `remaining` - outgoing track frames left to play, from the buffer first frame
+/// * `fade_frames` - crossfade length, in frames
+pub fn crossfade(
+    outgoing: &mut AudioBuffer<f32>,
+    incoming: &[Vec<f32>],
+    incoming_gain: f32,
+    remaining: u64,
+    fade_frames: u64,
+    curve: CrossfadeCurve,
+) {
+    let fade_start = remaining.saturating_sub(fade_frames) as usize;
+    let mut planes = outgoing.planes_mut();
+    for (plane, incoming_plane) in planes.planes().iter_mut().zip(incoming) {
+        for (frame, sample) in plane.iter_mut().enumerate().skip(fade_start) {
+            let frames_left = remaining.saturating_sub(frame as u64);
+            let progress = 1.0 - frames_left as f32 / fade_frames as f32;
+            let (outgoing_gain, fade_in_gain) = curve.gains(progress);
+            let incoming_sample = incoming_plane
+                .get(frame - fade_start)
+                .copied()
+                .unwrap_or(0.0);
+            *sample = *sample * outgoing_gain + incoming_sample * incoming_gain * fade_in_gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::audio::{Channels, Signal, SignalSpec};
+
+    #[test]
+    fn it_keeps_gains_sum_constant() {
+        for step in 0..=10 {
+            let progress = step as f32 / 10.0;
+            let (outgoing, incoming) = CrossfadeCurve::Linear.gains(progress);
+            assert!((outgoing + incoming - 1.0).abs() < 1e-6);
+            let (outgoing, incoming) = CrossfadeCurve::EqualPower.gains(progress);
+            assert!((outgoing.powi(2) + incoming.powi(2) - 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn it_mixes_incoming_frames() {
+        let spec = SignalSpec::new(44100, Channels::FRONT_LEFT);
+        let mut outgoing = AudioBuffer::<f32>::new(4, spec);
+        outgoing.render_reserved(Some(4));
+        outgoing.chan_mut(0).fill(1.0);
+        let incoming = vec![vec![0.5, 0.5, 0.5]];
+
+        crossfade(&mut outgoing, &incoming, 1.0, 4, 4, CrossfadeCurve::Linear);
+
+        // 4 frames left out of 4: fade just starts, incoming missing frame is silence
+        assert_eq!(outgoing.chan(0), &[1.0, 0.875, 0.75, 0.25]);
+    }
+
+    #[test]
+    fn it_starts_crossfade_inside_buffer() {
+        let spec = SignalSpec::new(44100, Channels::FRONT_LEFT);
+        let mut outgoing = AudioBuffer::<f32>::new(4, spec);
+        outgoing.render_reserved(Some(4));
+        outgoing.chan_mut(0).fill(1.0);
+        let incoming = vec![vec![0.5, 0.5]];
+
+        crossfade(&mut outgoing, &incoming, 1.0, 4, 2, CrossfadeCurve::Linear);
+
+        assert_eq!(outgoing.chan(0), &[1.0, 1.0, 1.0, 0.75]);
+    }
+}
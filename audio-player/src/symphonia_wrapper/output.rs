@@ -7,12 +7,17 @@
 
 //! Platform-dependant Audio Outputs
 //! Duplicated from symphonia-play project v.0.5.1
+//! Extended with headless outputs: WAV file and null sinks
 
+use std::fmt;
+use std::path::PathBuf;
 use std::result;
 
 use symphonia::core::audio::{AudioBufferRef, SignalSpec};
 use symphonia::core::units::Duration;
 
+use crate::audio_error::AudioError;
+
 pub trait AudioOutput {
     fn write(&mut self, decoded: AudioBufferRef<'_>) -> Result<()>;
     fn flush(&mut self);
@@ -29,6 +34,150 @@ pub enum AudioOutputError {
 
 pub type Result<T> = result::Result<T, AudioOutputError>;
 
+impl fmt::Display for AudioOutputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AudioOutputError::OpenStreamError => f.write_str("stream can't be opened"),
+            AudioOutputError::PlayStreamError => f.write_str("stream can't be played"),
+            AudioOutputError::StreamClosedError => f.write_str("stream closed"),
+        }
+    }
+}
+
+/// Details are logged by the output when the error occurs
+impl From<AudioOutputError> for AudioError {
+    fn from(err: AudioOutputError) -> AudioError {
+        AudioError::Output(err.to_string())
+    }
+}
+
+/// Where the player sends decoded audio
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum OutputBackend {
+    /// Platform audio device, PulseAudio on linux, cpal otherwise
+    #[default]
+    Device,
+    /// 32 bits float WAV file, holding exactly what would have been sent to the device
+    Wav(PathBuf),
+    /// Discards audio, paced as a real device would be
+    Null,
+}
+
+mod wav {
+    use super::{AudioOutput, AudioOutputError, Result};
+
+    use std::fs::File;
+    use std::io::BufWriter;
+    use std::path::Path;
+
+    use symphonia::core::audio::{AudioBufferRef, SampleBuffer, SignalSpec};
+    use symphonia::core::units::Duration;
+
+    use log::error;
+
+    pub struct WavFileOutput {
+        writer: hound::WavWriter<BufWriter<File>>,
+        sample_buf: SampleBuffer<f32>,
+    }
+
+    impl WavFileOutput {
+        pub fn try_open(
+            path: &Path,
+            spec: SignalSpec,
+            duration: Duration,
+        ) -> Result<Box<dyn AudioOutput>> {
+            let wav_spec = hound::WavSpec {
+                channels: spec.channels.count() as u16,
+                sample_rate: spec.rate,
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            };
+            match hound::WavWriter::create(path, wav_spec) {
+                Ok(writer) => Ok(Box::new(WavFileOutput {
+                    writer,
+                    sample_buf: SampleBuffer::<f32>::new(duration, spec),
+                })),
+                Err(err) => {
+                    error!("wav file {:?} open error: {}", path, err);
+
+                    Err(AudioOutputError::OpenStreamError)
+                }
+            }
+        }
+    }
+
+    impl AudioOutput for WavFileOutput {
+        fn write(&mut self, decoded: AudioBufferRef<'_>) -> Result<()> {
+            // Do nothing if there are no audio frames.
+            if decoded.frames() == 0 {
+                return Ok(());
+            }
+
+            // WAV data is interleaved, same as what is sent to the devices.
+            self.sample_buf.copy_interleaved_ref(decoded);
+
+            for sample in self.sample_buf.samples() {
+                if let Err(err) = self.writer.write_sample(*sample) {
+                    error!("wav file write error: {}", err);
+
+                    return Err(AudioOutputError::StreamClosedError);
+                }
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) {
+            // Keeps the header up to date, the file is finalized once the output is dropped.
+            if let Err(err) = self.writer.flush() {
+                error!("wav file flush error: {}", err);
+            }
+        }
+    }
+}
+
+mod null {
+    use super::{AudioOutput, Result};
+
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use symphonia::core::audio::{AudioBufferRef, SignalSpec};
+
+    pub struct NullOutput {
+        rate: u32,
+        /// Set on first write
+        started: Option<Instant>,
+        frames_written: u64,
+    }
+
+    impl NullOutput {
+        pub fn open(spec: SignalSpec) -> Box<dyn AudioOutput> {
+            Box::new(NullOutput {
+                rate: spec.rate,
+                started: None,
+                frames_written: 0,
+            })
+        }
+    }
+
+    impl AudioOutput for NullOutput {
+        fn write(&mut self, decoded: AudioBufferRef<'_>) -> Result<()> {
+            let started = *self.started.get_or_insert_with(Instant::now);
+            self.frames_written += decoded.frames() as u64;
+
+            // Block until the written frames would have been played by a device.
+            let played_at =
+                started + Duration::from_secs_f64(self.frames_written as f64 / self.rate as f64);
+            if let Some(wait) = played_at.checked_duration_since(Instant::now()) {
+                thread::sleep(wait);
+            }
+            Ok(())
+        }
+
+        fn flush(&mut self) {}
+    }
+}
+
 #[cfg(target_os = "linux")]
 mod pulseaudio {
     use super::{AudioOutput, AudioOutputError, Result};
@@ -95,6 +244,11 @@ mod pulseaudio {
                 }
             }
         }
+
+        /// PulseAudio server converts any stream rate to the sink one, keep the decoded rate.
+        pub fn output_rate(spec: SignalSpec) -> u32 {
+            spec.rate
+        }
     }
 
     impl AudioOutput for PulseAudioOutput {
@@ -226,6 +380,15 @@ mod cpal {
                 }
             }
         }
+
+        /// Default output device rate, decoded audio must be resampled to it.
+        pub fn output_rate(spec: SignalSpec) -> u32 {
+            cpal::default_host()
+                .default_output_device()
+                .and_then(|device| device.default_output_config().ok())
+                .map(|config| config.sample_rate().0)
+                .unwrap_or(spec.rate)
+        }
     }
 
     struct CpalAudioOutputImpl<T: AudioOutputSample>
@@ -325,12 +488,48 @@ mod cpal {
     }
 }
 
+/// Opens the audio output of the given backend
+pub fn try_open(
+    backend: &OutputBackend,
+    spec: SignalSpec,
+    duration: Duration,
+) -> Result<Box<dyn AudioOutput>> {
+    match backend {
+        OutputBackend::Device => try_open_device(spec, duration),
+        OutputBackend::Wav(path) => wav::WavFileOutput::try_open(path, spec, duration),
+        OutputBackend::Null => Ok(null::NullOutput::open(spec)),
+    }
+}
+
+/// Sample rate the audio output will be opened with for the given decoded signal.
+/// Headless outputs use `headless_rate`, the decoded rate if not set.
+pub fn get_output_rate(
+    backend: &OutputBackend,
+    spec: SignalSpec,
+    headless_rate: Option<u32>,
+) -> u32 {
+    match backend {
+        OutputBackend::Device => get_device_rate(spec),
+        OutputBackend::Wav(_) | OutputBackend::Null => headless_rate.unwrap_or(spec.rate),
+    }
+}
+
 #[cfg(target_os = "linux")]
-pub fn try_open(spec: SignalSpec, duration: Duration) -> Result<Box<dyn AudioOutput>> {
+fn try_open_device(spec: SignalSpec, duration: Duration) -> Result<Box<dyn AudioOutput>> {
     pulseaudio::PulseAudioOutput::try_open(spec, duration)
 }
 
 #[cfg(not(target_os = "linux"))]
-pub fn try_open(spec: SignalSpec, duration: Duration) -> Result<Box<dyn AudioOutput>> {
+fn try_open_device(spec: SignalSpec, duration: Duration) -> Result<Box<dyn AudioOutput>> {
     cpal::CpalAudioOutput::try_open(spec, duration)
 }
+
+#[cfg(target_os = "linux")]
+fn get_device_rate(spec: SignalSpec) -> u32 {
+    pulseaudio::PulseAudioOutput::output_rate(spec)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_device_rate(spec: SignalSpec) -> u32 {
+    cpal::CpalAudioOutput::output_rate(spec)
+}
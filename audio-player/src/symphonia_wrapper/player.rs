@@ -1,354 +1,819 @@
 //!
 //! Basic player, inspired by symphonia-player & symphonia getting-started
-//! known limitation :
-//! I had to add a hardcoded 48000Hz to sample spec in order to play it successfully on windows.(current configuration is : 2 canals, 24bits, 48000Hz)
-//! It is not really noticable as most music rate are near this one, but still, a resampling would be great (as all player does I guess)
+//! Decoded audio is resampled to the rate the audio output has been opened with (see resampler),
+//! as some platforms (i.e windows with cpal) only accept the device rate.
 //! For now I keep in mind those issues :
 //!    https://github.com/RustAudio/cpal/issues/593#issuecomment-1185260068
 //! bug detected https://github.com/pdeljanov/Symphonia/issues/43 =>merged look for a 0.5.2 ! (example in symphonia-play)
 //!
-//! If I want to go deeper on this way,
-//!  cpal::platform::Device::supported_output_configs(); => to get windows configuration
-//! use cpal::default_host;
-//! use cpal::platform::Device;
-//! + do the resampling...
 
 use log::warn;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
-use std::sync::mpsc::Sender;
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use symphonia::core::audio::SampleBuffer;
-use symphonia::core::codecs::{DecoderOptions, FinalizeResult, CODEC_TYPE_NULL};
+use std::time::Duration;
+use symphonia::core::audio::{
+    AsAudioBufferRef, AudioBuffer, AudioBufferRef, Channels, Signal, SignalSpec,
+};
+use symphonia::core::codecs::{Decoder, FinalizeResult, CODEC_TYPE_NULL};
 use symphonia::core::errors::{Error, Result};
-use symphonia::core::formats::{FormatReader, Track};
-use symphonia::core::units::TimeBase;
+use symphonia::core::formats::{FormatReader, SeekMode, SeekTo, Track};
+use symphonia::core::units::{Time, TimeBase};
 
 use lazy_static::lazy_static;
 use std::io::Write;
 
 use super::commons::{self};
-use super::output;
+use super::gain::{db_to_linear, GainStage, ReplayGain, ReplayGainMode};
+use super::metadata_parser;
+use super::mixer::{self, CrossfadeCurve};
+use super::output::{self, OutputBackend};
+use super::resampler::{ResampleQuality, Resampler};
+use super::sample_stream::{SampleLayout, SampleStream};
+use crate::audio_error::{AudioError, AudioResult};
+use crate::AudioTag;
 
-#[derive(Copy, Clone)]
-struct PlayTrackOptions {
-    track_id: u32,
-    seek_ts: u64,
+/// User settings applied while playing
+#[derive(Debug, Clone, Default)]
+pub struct PlaybackOptions {
+    /// Where decoded audio is sent
+    pub output_backend: OutputBackend,
+    /// Resampling algorithm, used when the audio output rate differs from the track one
+    pub resample_quality: ResampleQuality,
+    /// Sample rate of WAV & null outputs, track one if not set
+    pub output_rate: Option<u32>,
+    /// Hide the progress bar printed on stdout
+    pub no_progress: bool,
+    /// Software gain, in dB
+    pub volume_db: f32,
+    /// Start playing muted
+    pub muted: bool,
+    /// ReplayGain tags applied on top of the volume
+    pub replay_gain: ReplayGainMode,
+    /// Length of the crossfade between queued tracks, zero to play them gapless
+    pub crossfade: Duration,
+    pub crossfade_curve: CrossfadeCurve,
+    /// Track index to play, first supported track if not set
+    pub track: Option<usize>,
+    /// Time to start playing from
+    pub seek: Option<Duration>,
+    /// Time to stop playing at, i.e a CUE sheet track end
+    pub end: Option<Duration>,
 }
 
-pub fn get_file_samples(audio_path: &Path) -> Option<Box<Vec<f32>>> {
-    let mut probed = match commons::get_probe(audio_path) {
-        Ok(probe) => probe,
-        Err(err) => panic!("Unsupported format {}", err),
-    };
-    let mut format = probed.format;
-    // Get the default track.
-    let track = format.default_track().unwrap();
-    let track_id = track.id;
-    let decode_opts: DecoderOptions = Default::default();
-    // Create a decoder for the track.
-    let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &decode_opts)
-        .expect("unsupported codec");
-    let mut sample_buf = None;
-    let mut sample_array = Box::new(Vec::new());
-
-    let result = loop {
-        // Get the next packet from the format reader.
-        let packet = match format.next_packet() {
-            Ok(packet) => packet,
-            Err(err) => break Err(err),
-        };
-
-        // If the packet does not belong to the selected track, skip it.
-        if packet.track_id() != track_id {
-            continue;
-        }
-        // Decode the packet into audio samples, ignoring any decode errors.
-        match decoder.decode(&packet) {
-            Ok(audio_buf) => {
-                // The decoded audio samples may now be accessed via the audio buffer if per-channel
-                // slices of samples in their native decoded format is desired. Use-cases where
-                // the samples need to be accessed in an interleaved order or converted into
-                // another sample format, or a byte buffer is required, are covered by copying the
-                // audio buffer into a sample buffer or raw sample buffer, respectively. In the
-                // example below, we will copy the audio buffer into a sample buffer in an
-                // interleaved order while also converting to a f32 sample format.
-
-                // If this is the *first* decoded packet, create a sample buffer matching the
-                // decoded audio buffer format.
-                if sample_buf.is_none() {
-                    // Get the audio buffer specification.
-                    let spec = *audio_buf.spec();
-
-                    // Get the capacity of the decoded buffer. Note: This is capacity, not length!
-                    let duration = audio_buf.capacity() as u64;
-
-                    // Create the f32 sample buffer.
-                    sample_buf = Some(SampleBuffer::<f32>::new(duration, spec));
-                }
-                // Copy the decoded audio buffer into the sample buffer in an interleaved format.
-                if let Some(buf) = &mut sample_buf {
-                    buf.copy_interleaved_ref(audio_buf);
+/// Decodes the whole file into interleaved f32 samples
+pub fn get_file_samples(audio_path: &Path) -> AudioResult<Box<Vec<f32>>> {
+    get_file_range_samples(audio_path, None, None).map(Box::new)
+}
 
-                    sample_array.extend_from_slice(buf.samples());
-                }
-            }
-            Err(err) => break Err(err),
-        }
-    };
-    // Return if a fatal error occured.
-    ignore_end_of_stream_error(result).unwrap();
-    Some(sample_array)
+/// Decodes frames from `from` to `to` into interleaved f32 samples, without decoding
+/// the file from its start
+pub fn get_file_range_samples(
+    audio_path: &Path,
+    from: Option<Duration>,
+    to: Option<Duration>,
+) -> AudioResult<Vec<f32>> {
+    let mut sample_array = Vec::new();
+    for block in SampleStream::open_range(audio_path, SampleLayout::Interleaved, from, to)? {
+        sample_array.extend_from_slice(&block?.samples);
+    }
+    Ok(sample_array)
 }
 
 /// TODO create same method with an Arc<Mutex> to guarantee sync with audio player
 pub fn get_live_sample(
     audio_path: &Path,
     live_sample: Sender<(usize, usize, Vec<f32>)>,
-    live_sample_written: &mut Rc<usize>,
-) -> JoinHandle<()> {
-    let audio_p = audio_path.clone().to_owned();
-
-    let closure_get_live_sample =
-        move |packet_idx: usize, sample_buffer: &mut SampleBuffer<f32>| {
-            live_sample
-                .send((
-                    packet_idx,
-                    sample_buffer.len(),
-                    sample_buffer.samples().to_vec(),
-                ))
-                .unwrap();
-        };
+    _live_sample_written: &mut Rc<usize>,
+) -> JoinHandle<AudioResult<()>> {
+    let audio_p = audio_path.to_owned();
+
+    thread::spawn(move || {
+        let stream = SampleStream::open(audio_p.as_path(), SampleLayout::Interleaved)?;
+        for (block_idx, block) in stream.enumerate() {
+            let samples = block?.samples;
+            // Nobody listens anymore, stop decoding.
+            if live_sample
+                .send((block_idx + 1, samples.len(), samples))
+                .is_err()
+            {
+                break;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Plays an audio file until its end
+pub fn play_track(music_path: &Path, options: PlaybackOptions) -> AudioResult<i32> {
+    Player::new(music_path, options).wait()
+}
+
+/// Plays audio files one after the other, without gap between them
+pub fn play_queue(music_paths: &[PathBuf], options: PlaybackOptions) -> AudioResult<i32> {
+    Player::with_queue(music_paths, options).wait()
+}
+
+/// Commands sent by the Player handle to the playing thread
+#[derive(Debug, Copy, Clone)]
+enum PlayerCommand {
+    Pause,
+    Resume,
+    Stop,
+    Seek(Duration),
+    Volume(f32),
+    Mute(bool),
+}
 
-    let handler = thread::spawn(move || {
-        apply_on_sample(audio_p.as_path(), closure_get_live_sample);
-    });
-    handler
+/// Playing thread side of the Player handle
+struct PlayerLink {
+    commands: Receiver<PlayerCommand>,
+    position: Arc<Mutex<Duration>>,
 }
 
-/// No sound feedback
+/// Controllable player, decoding & playing audio files in its own thread.
 ///
-pub fn apply_on_sample<T>(audio_path: &Path, mut callback: T)
-where
-    T: FnMut(usize, &mut SampleBuffer<f32>),
-{
-    let mut probed = match commons::get_probe(audio_path) {
-        Ok(probe) => probe,
-        Err(err) => panic!("Unsupported format {}", err),
-    };
-    let mut format = probed.format;
-    // Get the default track.
-    let track = format.default_track().unwrap();
-    let track_id = track.id;
-    let decode_opts: DecoderOptions = Default::default();
-    let play_opts = PlayTrackOptions {
-        track_id: track_id,
-        seek_ts: 0.0 as u64,
-    };
-    // Create a decoder for the track.
-    let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &decode_opts)
-        .expect("unsupported codec");
-    let mut sample_buf = None;
+/// Dropping the player stops the playback.
+pub struct Player {
+    commands: Sender<PlayerCommand>,
+    position: Arc<Mutex<Duration>>,
+    handle: Option<JoinHandle<AudioResult<i32>>>,
+}
 
-    let mut packet_idx: usize = 0;
+impl Player {
+    /// Starts playing the audio file in a dedicated thread
+    pub fn new(music_path: &Path, options: PlaybackOptions) -> Player {
+        Player::with_queue(&[music_path.to_path_buf()], options)
+    }
 
-    let result = loop {
-        // Get the next packet from the format reader.
-        let packet = match format.next_packet() {
-            Ok(packet) => packet,
-            Err(err) => break Err(err),
+    /// Starts playing the audio files in a dedicated thread, in the given order.
+    /// Audio output is kept open between files so consecutive tracks play gapless.
+    pub fn with_queue(music_paths: &[PathBuf], options: PlaybackOptions) -> Player {
+        let (commands, receiver) = channel::<PlayerCommand>();
+        let position = Arc::new(Mutex::new(Duration::ZERO));
+        let link = PlayerLink {
+            commands: receiver,
+            position: Arc::clone(&position),
         };
+        let queue = music_paths.to_vec();
+        let handle = thread::spawn(move || play_files(&queue, &options, &link));
+        Player {
+            commands,
+            position,
+            handle: Some(handle),
+        }
+    }
 
-        // If the packet does not belong to the selected track, skip it.
-        if packet.track_id() != track_id {
-            continue;
+    pub fn pause(&self) {
+        self.send(PlayerCommand::Pause);
+    }
+
+    pub fn resume(&self) {
+        self.send(PlayerCommand::Resume);
+    }
+
+    pub fn stop(&self) {
+        self.send(PlayerCommand::Stop);
+    }
+
+    /// Jumps to the given time from the current track start
+    pub fn seek(&self, time: Duration) {
+        self.send(PlayerCommand::Seek(time));
+    }
+
+    /// Changes the software gain, in dB. Change is ramped to avoid clicks.
+    pub fn set_volume(&self, volume_db: f32) {
+        self.send(PlayerCommand::Volume(volume_db));
+    }
+
+    pub fn set_muted(&self, muted: bool) {
+        self.send(PlayerCommand::Mute(muted));
+    }
+
+    /// Time of the last audio packet sent to the audio output, from the current track start
+    pub fn position(&self) -> Duration {
+        *self.position.lock().expect("couldn't lock player position")
+    }
+
+    /// True once the queue is over or the player stopped
+    pub fn is_finished(&self) -> bool {
+        self.handle
+            .as_ref()
+            .is_none_or(|handle| handle.is_finished())
+    }
+
+    /// Blocks until the queue is over or the player stopped
+    pub fn wait(mut self) -> AudioResult<i32> {
+        match self.handle.take() {
+            Some(handle) => handle
+                .join()
+                .unwrap_or_else(|_| Err(AudioError::Output("player thread panicked".to_string()))),
+            None => Ok(0),
         }
-        // Decode the packet into audio samples, ignoring any decode errors.
-        match decoder.decode(&packet) {
-            Ok(mut audio_buf) => {
-                // The decoded audio samples may now be accessed via the audio buffer if per-channel
-                // slices of samples in their native decoded format is desired. Use-cases where
-                // the samples need to be accessed in an interleaved order or converted into
-                // another sample format, or a byte buffer is required, are covered by copying the
-                // audio buffer into a sample buffer or raw sample buffer, respectively. In the
-                // example below, we will copy the audio buffer into a sample buffer in an
-                // interleaved order while also converting to a f32 sample format.
-
-                // If this is the *first* decoded packet, create a sample buffer matching the
-                // decoded audio buffer format.
-                if sample_buf.is_none() {
-                    // Get the audio buffer specification.
-                    let spec = *audio_buf.spec();
-
-                    // Get the capacity of the decoded buffer. Note: This is capacity, not length!
-                    let duration = audio_buf.capacity() as u64;
-
-                    // Create the f32 sample buffer.
-                    sample_buf = Some(SampleBuffer::<f32>::new(duration, spec));
-                }
-                // Copy the decoded audio buffer into the sample buffer in an interleaved format.
-                if let Some(buf) = &mut sample_buf {
-                    packet_idx += 1;
-                    buf.copy_interleaved_ref(audio_buf);
-                    callback(packet_idx, buf);
-                }
+    }
+
+    fn send(&self, command: PlayerCommand) {
+        // Playing thread is gone once the track is over, nothing left to control.
+        let _ = self.commands.send(command);
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.stop();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// How a track playback ended
+enum TrackEnd {
+    /// Track played until its end, with the decoder verification code
+    Finished(i32),
+    /// Player asked to stop
+    Stopped,
+}
+
+/// Audio output, opened for a given decoded signal specification
+struct OutputStage {
+    output: Box<dyn output::AudioOutput>,
+    /// Only set if the audio output rate differs from the decoded one.
+    resampler: Option<Resampler>,
+    spec: SignalSpec,
+    duration: u64,
+}
+
+impl OutputStage {
+    fn open(
+        spec: SignalSpec,
+        duration: u64,
+        options: &PlaybackOptions,
+    ) -> AudioResult<OutputStage> {
+        let mut output_duration = duration;
+        let mut resampler = None;
+
+        // Resample if the output can't be opened at the track rate.
+        let output_rate =
+            output::get_output_rate(&options.output_backend, spec, options.output_rate);
+        if output_rate != spec.rate {
+            let resampling = Resampler::new(spec, output_rate, duration, options.resample_quality);
+            output_duration = resampling.output_frames_max();
+            resampler.replace(resampling);
+        }
+
+        // Try to open the audio output.
+        let output_spec = SignalSpec::new(output_rate, spec.channels);
+        Ok(OutputStage {
+            output: output::try_open(&options.output_backend, output_spec, output_duration)?,
+            resampler,
+            spec,
+            duration,
+        })
+    }
+
+    /// True if decoded buffers of this spec and capacity can be written as is
+    fn accepts(&self, spec: &SignalSpec, duration: u64) -> bool {
+        self.spec == *spec && duration <= self.duration
+    }
+
+    fn write(&mut self, decoded: AudioBufferRef<'_>) -> AudioResult<()> {
+        if let Some(resampler) = self.resampler.as_mut() {
+            if let Some(resampled) = resampler.resample(decoded) {
+                self.output.write(resampled)?;
             }
-            Err(err) => break Err(err),
+        } else {
+            self.output.write(decoded)?;
         }
-    };
-    // Return if a fatal error occured.
-    ignore_end_of_stream_error(result).unwrap();
+        Ok(())
+    }
+
+    /// Writes frames still pending in the resampler, then lets the output play them.
+    fn flush(&mut self) -> AudioResult<()> {
+        if let Some(resampled) = self.resampler.as_mut().and_then(|r| r.flush()) {
+            self.output.write(resampled)?;
+        }
+        self.output.flush();
+        Ok(())
+    }
 }
 
-pub fn play_track(
-    music_path: &Path,
-    live_sample: Arc<Mutex<(u64, (u64, u64, f64))>>, //TODO optionalize those param
-    start_play: Arc<Mutex<bool>>,                    //TODO optionalize those param
-) -> Result<i32> {
-    let probed = match commons::get_probe(music_path) {
-        Ok(probe) => probe,
-        Err(err) => panic!("Unsupported format {}", err),
+fn play_files(
+    music_paths: &[PathBuf],
+    options: &PlaybackOptions,
+    link: &PlayerLink,
+) -> AudioResult<i32> {
+    // The audio output is shared by all the tracks. First is None
+    let mut output_stage = None;
+    // Gain state is kept between tracks too, so runtime changes persist.
+    let mut gain = GainStage::new(options.volume_db, options.muted);
+    let mut code = 0;
+    // Start & end times only apply to the first track of the queue.
+    let mut next_options = options.clone();
+    next_options.seek = None;
+    next_options.end = None;
+
+    let mut queue = music_paths.iter();
+    let mut current = match queue.next() {
+        Some(music_path) => Some(TrackDecoder::open(music_path, options)?),
+        None => None,
     };
 
-    // Get the instantiated format reader.
-    let format = probed.format;
+    while let Some(mut track) = current.take() {
+        // Next track is opened once the current one reaches its crossfade, or once it's over.
+        let mut next = NextTrack {
+            music_path: queue.next().map(PathBuf::as_path),
+            options: &next_options,
+            decoder: None,
+        };
 
-    // Get the value of the track option, if provided.
-    let track = None;
-    let seek_time = None;
-    // Set the decoder options.
-    let decode_opts: DecoderOptions = Default::default();
-    // The audio output device. First is None
-    let mut audio_output = None;
-    {
-        if let Ok(start_p) = start_play.try_lock() {
-            println!("received starting to play !!");
-        } else {
-            println!("couldn't lock for playing");
+        if options.replay_gain != ReplayGainMode::Off {
+            gain.set_replay_gain(track.replay_gain.gain_db(options.replay_gain));
         }
+
+        // Play it!
+        match play(
+            &mut track,
+            &mut next,
+            &mut output_stage,
+            &mut gain,
+            options,
+            link,
+        )? {
+            TrackEnd::Finished(track_code) => code = code.max(track_code),
+            TrackEnd::Stopped => return Ok(0),
+        }
+
+        // Unreadable files are skipped, the queue goes on with the following ones.
+        current = next.into_decoder();
+        while current.is_none() {
+            match queue.next() {
+                Some(music_path) => current = open_or_skip(music_path, &next_options),
+                None => break,
+            }
+        }
+    }
+
+    if let Some(output_stage) = output_stage.as_mut() {
+        output_stage.flush()?;
     }
-    println!("playin");
-    // Play it!
-    play(
-        format,
-        &mut audio_output,
-        track,
-        seek_time,
-        &decode_opts,
-        live_sample,
-        false,
-    )
+    Ok(code)
 }
 
-fn play(
-    mut reader: Box<dyn FormatReader>,
-    audio_output: &mut Option<Box<dyn output::AudioOutput>>,
-    track_num: Option<usize>,
-    seek_time: Option<f64>,
-    decode_opts: &DecoderOptions,
-    live_sample: Arc<Mutex<(u64, (u64, u64, f64))>>,
-    no_progress: bool,
-) -> Result<i32> {
-    // If the user provided a track number, select that track if it exists, otherwise, select the
-    // first track with a known codec.
+/// Next queued track, opened on demand so that an unreadable file doesn't stop the queue
+struct NextTrack<'a> {
+    /// Set until the track is opened
+    music_path: Option<&'a Path>,
+    options: &'a PlaybackOptions,
+    decoder: Option<TrackDecoder>,
+}
+
+impl NextTrack<'_> {
+    /// Opens the track on first call
+    ///
+    /// # Returns
+    /// its decoder, None if there is no next track or it couldn't be opened
+    fn open(&mut self) -> Option<&mut TrackDecoder> {
+        if let Some(music_path) = self.music_path.take() {
+            self.decoder = open_or_skip(music_path, self.options);
+        }
+        self.decoder.as_mut()
+    }
+
+    fn into_decoder(mut self) -> Option<TrackDecoder> {
+        self.open();
+        self.decoder
+    }
+}
+
+/// Opens a queued track, logging why it's skipped if it can't be
+fn open_or_skip(music_path: &Path, options: &PlaybackOptions) -> Option<TrackDecoder> {
+    match TrackDecoder::open(music_path, options) {
+        Ok(decoder) => Some(decoder),
+        Err(err) => {
+            warn!("skipping {:?}: {}", music_path, err);
+            None
+        }
+    }
+}
+
+/// Decoder and properties of the track picked among the reader ones
+struct SelectedTrack {
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    tb: Option<TimeBase>,
+    dur: Option<u64>,
+    sample_rate: Option<u32>,
+    channels: Option<Channels>,
+}
+
+/// Selects the requested track if it exists, otherwise the first track with a known codec
+fn select_track(reader: &dyn FormatReader, track_num: Option<usize>) -> AudioResult<SelectedTrack> {
     let track = track_num
         .and_then(|t| reader.tracks().get(t))
         .or_else(|| first_supported_track(reader.tracks()))
-        .expect("no supported audio tracks");
-
-    let seek_ts = seek_time.unwrap_or(0.0);
+        .ok_or_else(|| AudioError::UnsupportedCodec("no supported audio tracks".to_string()))?;
 
     // Create a decoder for the track.
-    let mut decoder = symphonia::default::get_codecs()
-        .make(&track.codec_params, &decode_opts)
-        .expect("unsupported codec");
+    let decoder = commons::make_decoder(&track.codec_params)?;
 
-    // Store the track identifier, it will be used to filter packets.
-    let track_id = track.id;
+    Ok(SelectedTrack {
+        decoder,
+        // Store the track identifier, it will be used to filter packets.
+        track_id: track.id,
+        // Get the selected track's timebase and duration.
+        tb: track.codec_params.time_base,
+        dur: track
+            .codec_params
+            .n_frames
+            .map(|frames| track.codec_params.start_ts + frames),
+        sample_rate: track.codec_params.sample_rate,
+        channels: track.codec_params.channels,
+    })
+}
 
-    let play_opts = PlayTrackOptions {
-        track_id,
-        seek_ts: seek_ts as u64,
-    };
+/// Decoding state of a queued track
+struct TrackDecoder {
+    reader: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    tb: Option<TimeBase>,
+    /// Timestamp of the track end, if known
+    dur: Option<u64>,
+    /// Timestamp to stop playing at, if the track is cut before its end
+    end_ts: Option<u64>,
+    /// Timestamp of the first packet to play
+    seek_ts: u64,
+    sample_rate: Option<u32>,
+    channels: Option<Channels>,
+    /// Tracks of a same album are not crossfaded
+    album: Option<String>,
+    replay_gain: ReplayGain,
+    /// Frames decoded ahead during a crossfade, planar, not played yet
+    pending: Vec<Vec<f32>>,
+    pending_buf: Option<AudioBuffer<f32>>,
+}
+
+impl TrackDecoder {
+    fn open(music_path: &Path, options: &PlaybackOptions) -> AudioResult<TrackDecoder> {
+        let mut probed = commons::get_gapless_probe(music_path)?;
+
+        let mut tags = metadata_parser::get_probed_metadata(&mut probed, &ReplayGain::TAGS);
+        let replay_gain = ReplayGain::from_tags(&tags);
+        tags = metadata_parser::get_probed_metadata(&mut probed, &[AudioTag::Album]);
+        let album = tags.pop().flatten().map(|album| album.to_string());
+
+        // Get the instantiated format reader.
+        let mut reader = probed.format;
 
-    // Get the selected track's timebase and duration.
-    let tb = track.codec_params.time_base;
-    let dur = track
-        .codec_params
-        .n_frames
-        .map(|frames| track.codec_params.start_ts + frames);
+        let SelectedTrack {
+            decoder,
+            track_id,
+            tb,
+            dur,
+            sample_rate,
+            channels,
+        } = select_track(reader.as_ref(), options.track)?;
+
+        // If the user provided a start time, seek to it. Seek errors are not fatal, play from start.
+        let seek_ts = match options.seek {
+            Some(time) => match seek_to(reader.as_mut(), track_id, time) {
+                Ok(seek_ts) => {
+                    if let Some(tb) = tb {
+                        println!("Playing from {}", fmt_time(get_time(seek_ts, tb)));
+                    }
+                    seek_ts
+                }
+                Err(err) => {
+                    warn!("seek error: {}", err);
+                    0
+                }
+            },
+            None => 0,
+        };
+
+        // Playing stops at the end time, the track is considered as ending there.
+        let end_ts = options
+            .end
+            .zip(tb)
+            .map(|(end, tb)| tb.calc_timestamp(Time::from(end.as_secs_f64())))
+            .filter(|end_ts| dur.is_none_or(|dur| *end_ts < dur));
+        if options.end.is_some() && tb.is_none() {
+            warn!("unknown time base, can't stop at end time");
+        }
+
+        Ok(TrackDecoder {
+            reader,
+            decoder,
+            track_id,
+            tb,
+            dur: end_ts.or(dur),
+            end_ts,
+            seek_ts,
+            sample_rate,
+            channels,
+            album,
+            replay_gain,
+            pending: Vec::new(),
+            pending_buf: None,
+        })
+    }
+
+    /// Selects a track again and rebuilds its decoder, once the reader track list changed,
+    /// i.e a new physical stream of a chained Ogg file started
+    fn reset(&mut self) -> AudioResult<()> {
+        let selected = select_track(self.reader.as_ref(), None)?;
+        self.decoder = selected.decoder;
+        self.track_id = selected.track_id;
+        self.tb = selected.tb;
+        self.dur = selected.dur;
+        self.sample_rate = selected.sample_rate;
+        self.channels = selected.channels;
+        // Timestamps start over with the new stream.
+        self.seek_ts = 0;
+        self.end_ts = None;
+        Ok(())
+    }
+
+    /// True if the end of this track can be mixed with the start of the next one
+    fn can_crossfade(&self, next: &TrackDecoder, options: &PlaybackOptions) -> bool {
+        let same_album = self.album.is_some() && self.album == next.album;
+        !options.crossfade.is_zero()
+            && !same_album
+            && self.dur.is_some()
+            && self.sample_rate.is_some()
+            && self.sample_rate == next.sample_rate
+            && self.channels.map(|c| c.count()) == next.channels.map(|c| c.count())
+    }
+
+    /// Decodes the track start ahead of its playback
+    ///
+    /// # Returns
+    /// up to `frames` planar frames, less if the track is over
+    fn read_ahead(&mut self, frames: usize) -> AudioResult<Vec<Vec<f32>>> {
+        while self.pending.first().map_or(0, |plane| plane.len()) < frames {
+            let packet = match self.reader.next_packet() {
+                Ok(packet) => packet,
+                Err(Error::ResetRequired) => {
+                    self.reset()?;
+                    continue;
+                }
+                Err(err) => {
+                    ignore_end_of_stream_error(Err(err))?;
+                    break;
+                }
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let spec = *decoded.spec();
+                    let duration = decoded.capacity() as u64;
+                    let buffer = self
+                        .pending_buf
+                        .get_or_insert_with(|| AudioBuffer::<f32>::new(duration, spec));
+                    decoded.convert(buffer);
+                    self.pending.resize_with(spec.channels.count(), Vec::new);
+                    for (pending, plane) in self.pending.iter_mut().zip(buffer.planes().planes()) {
+                        pending.extend_from_slice(plane);
+                    }
+                }
+                Err(Error::DecodeError(err)) => warn!("decode error: {}", err),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        let frames = frames.min(self.pending.first().map_or(0, |plane| plane.len()));
+        Ok(self
+            .pending
+            .iter_mut()
+            .map(|pending| pending.drain(..frames).collect())
+            .collect())
+    }
+
+    /// Drops frames decoded ahead, and gets back to the track start
+    fn rewind(&mut self) -> Result<()> {
+        if self.pending_buf.is_some() {
+            self.seek_ts = seek_to(self.reader.as_mut(), self.track_id, Duration::ZERO)?;
+            self.decoder.reset();
+            self.pending.clear();
+            self.pending_buf = None;
+        }
+        Ok(())
+    }
+
+    /// Frames decoded ahead, to play before decoding the next packets
+    fn take_pending(&mut self) -> Option<AudioBuffer<f32>> {
+        let spec = *self.pending_buf.take()?.spec();
+        let frames = self.pending.first().map_or(0, |plane| plane.len());
+        let mut buffer = AudioBuffer::<f32>::new(frames as u64, spec);
+        buffer.render_reserved(Some(frames));
+        for (plane, pending) in buffer.planes_mut().planes().iter_mut().zip(&self.pending) {
+            plane.copy_from_slice(pending);
+        }
+        self.pending.clear();
+        Some(buffer)
+    }
+}
+
+/// Opens the audio output if needed, then writes decoded audio into it through the gain stage
+fn write_output(
+    output_stage: &mut Option<OutputStage>,
+    gain: &mut GainStage,
+    options: &PlaybackOptions,
+    decoded: AudioBufferRef<'_>,
+) -> AudioResult<()> {
+    // Get the audio buffer specification. This is a description of the decoded
+    // audio buffer's sample format and sample rate.
+    let spec = *decoded.spec();
+
+    // Get the capacity of the decoded buffer. Note that this is capacity, not
+    // length! The capacity of the decoded buffer is constant for the life of the
+    // decoder, but the length is not.
+    let duration = decoded.capacity() as u64;
+
+    // If the audio output is not open, or doesn't match this track, try to open it.
+    if !output_stage
+        .as_ref()
+        .is_some_and(|stage| stage.accepts(&spec, duration))
+    {
+        if let Some(previous) = output_stage.as_mut() {
+            previous.flush()?;
+        }
+        output_stage.replace(OutputStage::open(spec, duration, options)?);
+    }
+
+    match output_stage.as_mut() {
+        Some(output_stage) => output_stage.write(gain.apply(decoded)),
+        None => Ok(()),
+    }
+}
+
+/// Plays a track until its end
+///
+/// # Arguments
+/// * `next` - next queued track, opened when this track end is close enough to crossfade them.
+///   If it can't be opened or crossfaded, this track plays until its end without crossfade
+fn play(
+    track: &mut TrackDecoder,
+    next: &mut NextTrack<'_>,
+    output_stage: &mut Option<OutputStage>,
+    gain: &mut GainStage,
+    options: &PlaybackOptions,
+    link: &PlayerLink,
+) -> AudioResult<TrackEnd> {
+    // Frames decoded ahead while crossfading from the previous track.
+    if let Some(pending) = track.take_pending() {
+        write_output(output_stage, gain, options, pending.as_audio_buffer_ref())?;
+    }
+
+    // Set once the next track is opened, if both tracks can be crossfaded.
+    let mut crossfade = None;
+    // Outgoing track buffer, mixed with the incoming one.
+    let mut mix_buf: Option<AudioBuffer<f32>> = None;
+
+    let mut paused = false;
 
     let result = loop {
+        // Apply player commands before reading the next packet.
+        match receive_command(&link.commands, paused) {
+            Some(PlayerCommand::Pause) => {
+                paused = true;
+                continue;
+            }
+            Some(PlayerCommand::Resume) => {
+                paused = false;
+                continue;
+            }
+            Some(PlayerCommand::Stop) => return Ok(TrackEnd::Stopped),
+            Some(PlayerCommand::Seek(time)) => {
+                match seek_to(track.reader.as_mut(), track.track_id, time) {
+                    Ok(seek_ts) => {
+                        track.seek_ts = seek_ts;
+                        // Decoder state refers to packets preceding the seek, drop it.
+                        track.decoder.reset();
+                        // Crossfade will start again from the next track start.
+                        if let Some(Err(err)) = next.decoder.as_mut().map(TrackDecoder::rewind) {
+                            warn!("seek error: {}", err);
+                        }
+                    }
+                    Err(err) => warn!("seek error: {}", err),
+                }
+                continue;
+            }
+            Some(PlayerCommand::Volume(volume_db)) => {
+                gain.set_volume(volume_db);
+                continue;
+            }
+            Some(PlayerCommand::Mute(muted)) => {
+                gain.set_muted(muted);
+                continue;
+            }
+            None => (),
+        }
+
         // Get the next packet from the media format.
-        let packet = match reader.next_packet() {
+        let packet = match track.reader.next_packet() {
             Ok(packet) => packet,
-            Err(err @ Error::ResetRequired) => {
-                // The track list has been changed. Re-examine it and create a new set of decoders,
-                // then restart the decode loop. This is an advanced feature and it is not
-                // unreasonable to consider this "the end." As of v0.5.0, the only usage of this is
-                // for chained OGG physical streams.
-                // unimplemented!();
-                break Err(err);
+            Err(Error::ResetRequired) => {
+                // The track list has been changed, i.e a new physical stream of a chained Ogg
+                // file. Re-examine it, create a new decoder, then restart the decode loop.
+                track.reset()?;
+                // The new stream may not have the same signal specification.
+                mix_buf = None;
+                continue;
             }
             Err(err) => break Err(err),
         };
 
         // If the packet does not belong to the selected track, skip over it.
-        if packet.track_id() != track_id {
+        if packet.track_id() != track.track_id {
             continue;
         }
+        // Stop at the end time, as if the track was over.
+        if track.end_ts.is_some_and(|end_ts| packet.ts() >= end_ts) {
+            break Ok(());
+        }
+
+        // Open the next track once this packet reaches the crossfade.
+        if crossfade.is_none() && !options.crossfade.is_zero() {
+            let fade_frames =
+                options.crossfade.as_secs_f64() * track.sample_rate.unwrap_or(0) as f64;
+            let fade_start = track.dur.map(|dur| dur.saturating_sub(fade_frames as u64));
+            if fade_start.is_some_and(|fade_start| packet.ts() + packet.dur() > fade_start) {
+                crossfade = Some(get_incoming_gain(track, next, options));
+            }
+        }
 
         // Consume any new metadata that has been read since the last packet.
-        while !reader.metadata().is_latest() {
+        while !track.reader.metadata().is_latest() {
             // Pop the old head of the metadata queue.
-            reader.metadata().pop();
+            track.reader.metadata().pop();
             // Consume the new metadata at the head of the metadata queue.
+            if let Some(rev) = track.reader.metadata().current() {
+                if !options.no_progress {
+                    println!();
+                }
+                metadata_parser::print_update(rev);
+            }
         }
 
         // Decode the packet into audio samples.
-        match decoder.decode(&packet) {
+        match track.decoder.decode(&packet) {
             Ok(decoded) => {
-                // Consume the decoded audio samples (see below).
-                // If the audio output is not open, try to open it.
-                if audio_output.is_none() {
-                    // Get the audio buffer specification. This is a description of the decoded
-                    // audio buffer's sample format and sample rate.
-                    let mut spec = *decoded.spec();
-                    spec.rate = 48000;
-                    //println!("spec are {:?}", spec);
-
-                    // Get the capacity of the decoded buffer. Note that this is capacity, not
-                    // length! The capacity of the decoded buffer is constant for the life of the
-                    // decoder, but the length is not.
-                    let duration = decoded.capacity() as u64;
-
-                    // Try to open the audio output.
-                    audio_output.replace(output::try_open(spec, duration).unwrap());
-                } else {
-                    // TODO: Check the audio spec. and duration hasn't changed.
-                }
-
-                // Write the decoded audio samples to the audio output if the presentation timestamp
-                // for the packet is >= the seeked position (0 if not seeking).
-                if packet.ts() >= play_opts.seek_ts {
-                    if !no_progress {
-                        print_progress(packet.ts(), dur, tb);
+                // Write the decoded audio samples to the audio output from the seeked position
+                // (0 if not seeking), which may be in the middle of this packet.
+                let skipped = track.seek_ts.saturating_sub(packet.ts());
+                let frames = (decoded.frames() as u64).saturating_sub(skipped);
+                if frames > 0 {
+                    let ts = packet.ts() + skipped;
+                    if !options.no_progress {
+                        print_progress(ts, track.dur, track.tb);
                     }
-                    if let Some(tb) = tb {
-                        let formatted_time = get_timestamp_formatted(packet.ts(), tb);
-
-                        if let Some(audio_output) = audio_output {
-                            {
-                                let mut live_sample_mut = live_sample
-                                    .try_lock()
-                                    .expect("couldn't acquire write lock playin sync");
-                                *live_sample_mut = (packet.ts(), formatted_time);
-                            }
-                            audio_output.write(decoded).unwrap()
+                    if let Some(tb) = track.tb {
+                        let mut position =
+                            link.position.lock().expect("couldn't lock player position");
+                        *position = get_time(ts, tb);
+                    }
+
+                    let spec = *decoded.spec();
+                    let fade_frames = (options.crossfade.as_secs_f64() * spec.rate as f64) as u64;
+                    let remaining = track.dur.unwrap_or(u64::MAX).saturating_sub(ts);
+                    // Crossfade may start in the middle of this packet.
+                    let fade_start = remaining.saturating_sub(fade_frames);
+                    let incoming_gain = crossfade.flatten();
+                    let crossfading = incoming_gain.is_some() && fade_start < frames;
+                    // End time may be in the middle of this packet too.
+                    let cut = track.end_ts.is_some() && remaining < frames;
+                    if crossfading || cut || skipped > 0 {
+                        let duration = decoded.capacity() as u64;
+                        let mix =
+                            mix_buf.get_or_insert_with(|| AudioBuffer::<f32>::new(duration, spec));
+                        decoded.convert(mix);
+                        if cut {
+                            mix.truncate((skipped + remaining) as usize);
+                        }
+                        mix.shift(skipped as usize);
+                        if let Some(next) = next.decoder.as_mut().filter(|_| crossfading) {
+                            let needed = (mix.frames() as u64).saturating_sub(fade_start);
+                            let incoming = next.read_ahead(needed as usize).unwrap_or_else(|err| {
+                                warn!("crossfade decode error: {}", err);
+                                Vec::new()
+                            });
+                            mixer::crossfade(
+                                mix,
+                                &incoming,
+                                incoming_gain.unwrap_or(1.0),
+                                remaining,
+                                fade_frames,
+                                options.crossfade_curve,
+                            );
                         }
+                        write_output(output_stage, gain, options, mix.as_audio_buffer_ref())?;
+                    } else {
+                        write_output(output_stage, gain, options, decoded)?;
                     }
                 }
             }
@@ -364,7 +829,28 @@ fn play(
     // Return if a fatal error occured.
     ignore_end_of_stream_error(result)?;
     // Finalize the decoder and return the verification result if it's been enabled.
-    do_verification(decoder.finalize())
+    Ok(TrackEnd::Finished(do_verification(
+        track.decoder.finalize(),
+    )?))
+}
+
+/// Opens the next track to crossfade it with this one
+///
+/// # Returns
+/// gain applied on the next track frames mixed into this track, None if they can't be crossfaded
+fn get_incoming_gain(
+    track: &TrackDecoder,
+    next: &mut NextTrack<'_>,
+    options: &PlaybackOptions,
+) -> Option<f32> {
+    let next = next
+        .open()
+        .filter(|next| track.can_crossfade(next, options))?;
+    // Next track ReplayGain is applied on its frames mixed into this track.
+    Some(match options.replay_gain {
+        ReplayGainMode::Off => 1.0,
+        mode => db_to_linear(next.replay_gain.gain_db(mode) - track.replay_gain.gain_db(mode)),
+    })
 }
 
 fn first_supported_track(tracks: &[Track]) -> Option<&Track> {
@@ -375,14 +861,8 @@ fn first_supported_track(tracks: &[Track]) -> Option<&Track> {
 
 fn ignore_end_of_stream_error(result: Result<()>) -> Result<()> {
     match result {
-        Err(Error::IoError(err))
-            if err.kind() == std::io::ErrorKind::UnexpectedEof
-                && err.to_string() == "end of stream" =>
-        {
-            // Do not treat "end of stream" as a fatal error. It's the currently only way a
-            // format reader can indicate the media is complete.
-            Ok(())
-        }
+        // Do not treat "end of stream" as a fatal error.
+        Err(err) if commons::is_end_of_stream(&err) => Ok(()),
         _ => result,
     }
 }
@@ -400,13 +880,43 @@ fn do_verification(finalization: FinalizeResult) -> Result<i32> {
     }
 }
 
-fn get_timestamp_formatted(ts: u64, tb: TimeBase) -> (u64, u64, f64) {
+/// Waits for a command while paused, only polls otherwise
+fn receive_command(commands: &Receiver<PlayerCommand>, paused: bool) -> Option<PlayerCommand> {
+    if paused {
+        // A dropped Player handle can't resume playback anymore.
+        return Some(commands.recv().unwrap_or(PlayerCommand::Stop));
+    }
+    match commands.try_recv() {
+        Ok(command) => Some(command),
+        Err(TryRecvError::Empty) => None,
+        Err(TryRecvError::Disconnected) => Some(PlayerCommand::Stop),
+    }
+}
+
+/// Accurate seek of the track
+///
+/// # Returns
+/// timestamp of the first packet to play
+fn seek_to(reader: &mut dyn FormatReader, track_id: u32, time: Duration) -> Result<u64> {
+    let seek_to = SeekTo::Time {
+        time: Time::from(time.as_secs_f64()),
+        track_id: Some(track_id),
+    };
+    let seeked_to = reader.seek(SeekMode::Accurate, seek_to)?;
+    Ok(seeked_to.required_ts)
+}
+
+fn get_time(ts: u64, tb: TimeBase) -> Duration {
     let t = tb.calc_time(ts);
+    Duration::from_secs(t.seconds) + Duration::from_secs_f64(t.frac)
+}
 
-    let hours = t.seconds / (60 * 60);
-    let mins = (t.seconds % (60 * 60)) / 60;
-    let secs = f64::from((t.seconds % 60) as u32) + t.frac;
-    (hours, mins, secs)
+fn fmt_time(time: Duration) -> String {
+    let seconds = time.as_secs();
+    let hours = seconds / (60 * 60);
+    let mins = (seconds % (60 * 60)) / 60;
+    let secs = (seconds % 60) as f64 + f64::from(time.subsec_millis()) / 1000.0;
+    format!("{}:{:0>2}:{:0>6.3}", hours, mins, secs)
 }
 
 fn print_progress(ts: u64, dur: Option<u64>, tb: Option<TimeBase>) {
@@ -477,3 +987,208 @@ fn print_progress(ts: u64, dur: Option<u64>, tb: Option<TimeBase>) {
     // Flush immediately since stdout is buffered.
     output.flush().unwrap();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_path, write_wav};
+    use std::f32::consts::PI;
+    use std::time::Instant;
+
+    const RATE: u32 = 44100;
+
+    /// Writes a stereo sine tone into a 32 bits float WAV file, returns its interleaved samples
+    fn write_sine(path: &Path, freq: f32, seconds: f32) -> Vec<f32> {
+        let frames: Vec<[f32; 2]> = (0..(RATE as f32 * seconds) as usize)
+            .map(|i| {
+                let sample = 0.5 * (2.0 * PI * freq * i as f32 / RATE as f32).sin();
+                [sample, -sample]
+            })
+            .collect();
+        write_wav(path, RATE, frames.iter().copied());
+        frames.concat()
+    }
+
+    fn read_wav(path: &Path) -> Vec<f32> {
+        hound::WavReader::open(path)
+            .unwrap()
+            .samples::<f32>()
+            .map(|sample| sample.unwrap())
+            .collect()
+    }
+
+    fn headless_options(output_backend: OutputBackend) -> PlaybackOptions {
+        PlaybackOptions {
+            output_backend,
+            no_progress: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_writes_decoded_audio_bit_exact() {
+        let input = temp_path("bit-exact-in.wav");
+        let output = temp_path("bit-exact-out.wav");
+        let samples = write_sine(&input, 440.0, 1.0);
+
+        let options = headless_options(OutputBackend::Wav(output.clone()));
+        assert_eq!(play_track(&input, options).unwrap(), 0);
+
+        assert_eq!(read_wav(&output), samples);
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_resamples_to_output_rate() {
+        let input = temp_path("resample-in.wav");
+        let output = temp_path("resample-out.wav");
+        write_sine(&input, 440.0, 1.0);
+
+        let options = PlaybackOptions {
+            output_rate: Some(48000),
+            ..headless_options(OutputBackend::Wav(output.clone()))
+        };
+        assert_eq!(play_track(&input, options).unwrap(), 0);
+
+        assert_eq!(
+            hound::WavReader::open(&output).unwrap().spec().sample_rate,
+            48000
+        );
+        let left: Vec<f32> = read_wav(&output).into_iter().step_by(2).collect();
+        assert!(left.len().abs_diff(48000) <= 48);
+        // one upward zero crossing per period
+        let crossings = left
+            .windows(2)
+            .filter(|w| w[0] < 0.0 && w[1] >= 0.0)
+            .count();
+        assert!(crossings.abs_diff(440) <= 1);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_reports_unwritable_output() {
+        let input = temp_path("unwritable-in.wav");
+        write_sine(&input, 440.0, 0.2);
+        let output = std::env::temp_dir().join("audio-player-missing-dir/out.wav");
+
+        let options = headless_options(OutputBackend::Wav(output));
+        assert!(matches!(
+            play_track(&input, options),
+            Err(AudioError::Output(_))
+        ));
+        let _ = std::fs::remove_file(input);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_plays_queue_without_gap() {
+        let first = temp_path("queue-first.wav");
+        let second = temp_path("queue-second.wav");
+        let output = temp_path("queue-out.wav");
+        let mut samples = write_sine(&first, 440.0, 0.7);
+        samples.extend(write_sine(&second, 880.0, 0.3));
+
+        let options = headless_options(OutputBackend::Wav(output.clone()));
+        play_queue(&[first.clone(), second.clone()], options).unwrap();
+
+        assert_eq!(read_wav(&output), samples);
+        for path in [first, second, output] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_crossfades_queued_tracks() {
+        let first = temp_path("crossfade-first.wav");
+        let second = temp_path("crossfade-second.wav");
+        let output = temp_path("crossfade-out.wav");
+        let first_samples = write_sine(&first, 440.0, 1.0);
+        let second_samples = write_sine(&second, 880.0, 1.0);
+
+        let options = PlaybackOptions {
+            crossfade: Duration::from_millis(500),
+            ..headless_options(OutputBackend::Wav(output.clone()))
+        };
+        play_queue(&[first.clone(), second.clone()], options).unwrap();
+
+        // half a second of stereo samples overlaps
+        let overlap = RATE as usize;
+        let mixed = read_wav(&output);
+        assert_eq!(
+            mixed.len(),
+            first_samples.len() + second_samples.len() - overlap
+        );
+        let fade_start = first_samples.len() - overlap;
+        assert_eq!(mixed[..fade_start], first_samples[..fade_start]);
+        assert_eq!(mixed[first_samples.len()..], second_samples[overlap..]);
+        // linear crossfade middle
+        let middle = fade_start + overlap / 2;
+        let expected = 0.5 * first_samples[middle] + 0.5 * second_samples[overlap / 2];
+        assert!((mixed[middle] - expected).abs() < 1e-3);
+
+        for path in [first, second, output] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_skips_unreadable_queued_track() {
+        let first = temp_path("skip-first.wav");
+        let missing = temp_path("skip-missing.wav");
+        let second = temp_path("skip-second.wav");
+        let output = temp_path("skip-out.wav");
+        let mut samples = write_sine(&first, 440.0, 1.0);
+        samples.extend(write_sine(&second, 880.0, 0.5));
+
+        let options = PlaybackOptions {
+            crossfade: Duration::from_millis(500),
+            ..headless_options(OutputBackend::Wav(output.clone()))
+        };
+        let queue = [first.clone(), missing, second.clone()];
+        assert_eq!(play_queue(&queue, options).unwrap(), 0);
+
+        // No crossfade with the unreadable track, the next one follows without gap.
+        assert_eq!(read_wav(&output), samples);
+        for path in [first, second, output] {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_paces_null_output_in_real_time() {
+        let input = temp_path("null-pacing.wav");
+        write_sine(&input, 440.0, 0.5);
+
+        let started = Instant::now();
+        play_track(&input, headless_options(OutputBackend::Null)).unwrap();
+
+        assert!(started.elapsed() >= Duration::from_millis(450));
+        let _ = std::fs::remove_file(input);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_stops_when_player_is_dropped() {
+        let input = temp_path("stop-on-drop.wav");
+        write_sine(&input, 440.0, 5.0);
+
+        let started = Instant::now();
+        let player = Player::new(&input, headless_options(OutputBackend::Null));
+        thread::sleep(Duration::from_millis(200));
+        assert!(!player.is_finished());
+        assert!(player.position() > Duration::ZERO);
+        drop(player);
+
+        assert!(started.elapsed() < Duration::from_secs(2));
+        let _ = std::fs::remove_file(input);
+    }
+}
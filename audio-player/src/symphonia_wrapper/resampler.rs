@@ -0,0 +1,301 @@
+//!
+//! Resampling stage between the decoder and the audio output, inspired by symphonia-play resampler.
+//! Decoded buffers are converted from the track sample rate to the rate the output was opened with,
+//! so a 44.1kHz track played on a 48kHz sink keeps its pitch and speed.
+//!
+
+use clap::ValueEnum;
+use rubato::{
+    FftFixedIn, SincFixedIn, SincInterpolationParameters, SincInterpolationType, VecResampler,
+    WindowFunction,
+};
+use symphonia::core::audio::{AsAudioBufferRef, AudioBuffer, AudioBufferRef, Signal, SignalSpec};
+use symphonia::core::units::Duration;
+
+/// Resampling algorithm, from the cheapest to the most accurate one
+#[derive(Debug, Clone, Copy, PartialEq, Default, ValueEnum)]
+pub enum ResampleQuality {
+    /// FFT based polyphase resampler
+    Fast,
+    /// Windowed sinc resampler, linear interpolation between sinc points
+    #[default]
+    Balanced,
+    /// Windowed sinc resampler with longer filter and cubic interpolation
+    Best,
+}
+
+pub struct Resampler {
+    resampler: Box<dyn VecResampler<f32>>,
+    quality: ResampleQuality,
+    /// Decoded buffer converted to f32, same spec as the decoder
+    converted: AudioBuffer<f32>,
+    /// Pending planar input frames, waiting for a full resampler chunk
+    input: Vec<Vec<f32>>,
+    /// Planar resampler output
+    output: Vec<Vec<f32>>,
+    /// Resampled buffer handed to the audio output
+    resampled: AudioBuffer<f32>,
+    from_rate: usize,
+    to_rate: usize,
+    /// Frames received so far
+    input_frames: usize,
+    /// Frames handed to the audio output so far
+    output_frames: usize,
+}
+
+impl Resampler {
+    /// # Arguments
+    /// * `spec` - decoded signal specification, its rate is the input rate
+    /// * `to_sample_rate` - rate the audio output was opened with
+    /// * `duration` - decoded buffer capacity, used as resampler chunk size
+    /// * `quality` - resampling algorithm
+    pub fn new(
+        spec: SignalSpec,
+        to_sample_rate: u32,
+        duration: Duration,
+        quality: ResampleQuality,
+    ) -> Resampler {
+        let num_channels = spec.channels.count();
+        let chunk_size = duration as usize;
+        let from_rate = spec.rate as usize;
+        let to_rate = to_sample_rate as usize;
+
+        let resampler: Box<dyn VecResampler<f32>> = match quality {
+            ResampleQuality::Fast => Box::new(
+                FftFixedIn::<f32>::new(from_rate, to_rate, chunk_size, 2, num_channels)
+                    .expect("invalid fft resampler configuration"),
+            ),
+            ResampleQuality::Balanced => Box::new(
+                SincFixedIn::<f32>::new(
+                    to_rate as f64 / from_rate as f64,
+                    1.0,
+                    SincInterpolationParameters {
+                        sinc_len: 128,
+                        f_cutoff: 0.95,
+                        oversampling_factor: 128,
+                        interpolation: SincInterpolationType::Linear,
+                        window: WindowFunction::Blackman2,
+                    },
+                    chunk_size,
+                    num_channels,
+                )
+                .expect("invalid sinc resampler configuration"),
+            ),
+            ResampleQuality::Best => Box::new(
+                SincFixedIn::<f32>::new(
+                    to_rate as f64 / from_rate as f64,
+                    1.0,
+                    SincInterpolationParameters {
+                        sinc_len: 256,
+                        f_cutoff: 0.95,
+                        oversampling_factor: 256,
+                        interpolation: SincInterpolationType::Cubic,
+                        window: WindowFunction::BlackmanHarris2,
+                    },
+                    chunk_size,
+                    num_channels,
+                )
+                .expect("invalid sinc resampler configuration"),
+            ),
+        };
+
+        let output = resampler.output_buffer_allocate(true);
+        let resampled = AudioBuffer::<f32>::new(
+            resampler.output_frames_max() as Duration,
+            SignalSpec::new(to_sample_rate, spec.channels),
+        );
+
+        Resampler {
+            resampler,
+            quality,
+            converted: AudioBuffer::<f32>::new(duration, spec),
+            input: vec![Vec::with_capacity(2 * chunk_size); num_channels],
+            output,
+            resampled,
+            from_rate,
+            to_rate,
+            input_frames: 0,
+            output_frames: 0,
+        }
+    }
+
+    /// Maximum frames count a resampled buffer can hold, audio output must be opened accordingly.
+    pub fn output_frames_max(&self) -> Duration {
+        self.resampler.output_frames_max() as Duration
+    }
+
+    /// Frames the resampled signal lags behind the input one, first resampled frames are silent.
+    /// Sinc resamplers start interpolating centred on the first input frame, so they don't lag.
+    pub fn output_delay(&self) -> usize {
+        match self.quality {
+            ResampleQuality::Fast => self.resampler.output_delay(),
+            ResampleQuality::Balanced | ResampleQuality::Best => 0,
+        }
+    }
+
+    /// Resamples a decoded buffer.
+    ///
+    /// # Returns
+    /// None while the resampler is still waiting for enough frames to fill a chunk.
+    pub fn resample(&mut self, decoded: AudioBufferRef<'_>) -> Option<AudioBufferRef<'_>> {
+        decoded.convert(&mut self.converted);
+        self.input_frames += self.converted.frames();
+        for (pending, plane) in self.input.iter_mut().zip(self.converted.planes().planes()) {
+            pending.extend_from_slice(plane);
+        }
+        self.process()
+    }
+
+    /// Resamples planar frames, one buffer per channel, at most `duration` frames per call.
+    ///
+    /// # Returns
+    /// None while the resampler is still waiting for enough frames to fill a chunk.
+    pub fn resample_planes(&mut self, planes: &[Vec<f32>]) -> Option<AudioBufferRef<'_>> {
+        self.input_frames += planes.first().map_or(0, Vec::len);
+        for (pending, plane) in self.input.iter_mut().zip(planes) {
+            pending.extend_from_slice(plane);
+        }
+        self.process()
+    }
+
+    fn process(&mut self) -> Option<AudioBufferRef<'_>> {
+        if self.input[0].len() < self.resampler.input_frames_next() {
+            return None;
+        }
+
+        let (consumed, produced) = self
+            .resampler
+            .process_into_buffer(&self.input, &mut self.output, None)
+            .expect("resampler buffers are sized at construction");
+        for pending in self.input.iter_mut() {
+            pending.drain(..consumed);
+        }
+        Some(self.fill_resampled(produced))
+    }
+
+    /// Resamples remaining frames, padded with silence. To call once the decoder reached the end.
+    /// Padding resampled past the input end, output delay included, is dropped.
+    pub fn flush(&mut self) -> Option<AudioBufferRef<'_>> {
+        if self.input[0].is_empty() {
+            return None;
+        }
+
+        let (_, produced) = self
+            .resampler
+            .process_partial_into_buffer(Some(&self.input), &mut self.output, None)
+            .expect("resampler buffers are sized at construction");
+        for pending in self.input.iter_mut() {
+            pending.clear();
+        }
+        let expected = (self.input_frames * self.to_rate + self.from_rate / 2) / self.from_rate
+            + self.output_delay();
+        let remaining = expected.saturating_sub(self.output_frames);
+        Some(self.fill_resampled(produced.min(remaining)))
+    }
+
+    fn fill_resampled(&mut self, frames: usize) -> AudioBufferRef<'_> {
+        self.output_frames += frames;
+        self.resampled.clear();
+        self.resampled.render_reserved(Some(frames));
+        for (plane, resampled) in self
+            .resampled
+            .planes_mut()
+            .planes()
+            .iter_mut()
+            .zip(&self.output)
+        {
+            plane.copy_from_slice(&resampled[..frames]);
+        }
+        self.resampled.as_audio_buffer_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use symphonia::core::audio::Channels;
+
+    const CHUNK: usize = 1152;
+
+    /// Builds a stereo sine tone, split into decoder sized chunks
+    fn sine_chunks(freq: f32, rate: u32, seconds: f32) -> Vec<AudioBuffer<f32>> {
+        let spec = SignalSpec::new(rate, Channels::FRONT_LEFT | Channels::FRONT_RIGHT);
+        let total = (rate as f32 * seconds) as usize;
+        let mut chunks = Vec::new();
+        let mut idx = 0;
+        while idx < total {
+            let frames = CHUNK.min(total - idx);
+            let mut buf = AudioBuffer::<f32>::new(CHUNK as Duration, spec);
+            buf.render_reserved(Some(frames));
+            for plane in buf.planes_mut().planes().iter_mut() {
+                for (i, sample) in plane.iter_mut().enumerate() {
+                    *sample = 0.5 * (2.0 * PI * freq * (idx + i) as f32 / rate as f32).sin();
+                }
+            }
+            idx += frames;
+            chunks.push(buf);
+        }
+        chunks
+    }
+
+    fn resample_all(
+        chunks: &[AudioBuffer<f32>],
+        to_rate: u32,
+        quality: ResampleQuality,
+    ) -> Vec<f32> {
+        let spec = *chunks[0].spec();
+        let mut resampler = Resampler::new(spec, to_rate, CHUNK as Duration, quality);
+        let mut left = Vec::new();
+        for chunk in chunks {
+            if let Some(AudioBufferRef::F32(buf)) = resampler.resample(chunk.as_audio_buffer_ref())
+            {
+                assert_eq!(buf.spec().rate, to_rate);
+                left.extend_from_slice(buf.chan(0));
+            }
+        }
+        if let Some(AudioBufferRef::F32(buf)) = resampler.flush() {
+            left.extend_from_slice(buf.chan(0));
+        }
+        left
+    }
+
+    /// Frequency estimation by counting rising zero crossings, skipping resampler warm-up
+    fn estimate_frequency(samples: &[f32], rate: u32) -> f32 {
+        let steady = &samples[rate as usize / 10..samples.len() - rate as usize / 10];
+        let crossings = steady
+            .windows(2)
+            .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+            .count();
+        crossings as f32 * rate as f32 / steady.len() as f32
+    }
+
+    #[test]
+    fn it_keeps_sine_frequency_when_upsampling() {
+        let chunks = sine_chunks(1000.0, 44100, 2.0);
+        for quality in [
+            ResampleQuality::Fast,
+            ResampleQuality::Balanced,
+            ResampleQuality::Best,
+        ] {
+            let resampled = resample_all(&chunks, 48000, quality);
+            let freq = estimate_frequency(&resampled, 48000);
+            assert!(
+                (freq - 1000.0).abs() < 2.0,
+                "{:?} resampled tone is {} Hz",
+                quality,
+                freq
+            );
+            // 2 seconds of audio, whatever the rate
+            assert!((resampled.len() as i64 - 96000).abs() < 2 * CHUNK as i64);
+        }
+    }
+
+    #[test]
+    fn it_keeps_sine_frequency_when_downsampling() {
+        let chunks = sine_chunks(440.0, 48000, 2.0);
+        let resampled = resample_all(&chunks, 44100, ResampleQuality::default());
+        let freq = estimate_frequency(&resampled, 44100);
+        assert!((freq - 440.0).abs() < 2.0, "resampled tone is {} Hz", freq);
+    }
+}
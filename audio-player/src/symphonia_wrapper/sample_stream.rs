@@ -0,0 +1,472 @@
+//!
+//! Pull-based decoding, one block of samples per packet, so that a whole file is
+//! processed in constant memory. Samples are i16, i32, f32 or f64, f32 being the default.
+//!
+
+use std::path::Path;
+use std::time::Duration;
+use symphonia::core::audio::{SampleBuffer, SignalSpec};
+use symphonia::core::codecs::Decoder;
+use symphonia::core::conv::{ConvertibleSample, FromSample};
+use symphonia::core::formats::{FormatReader, SeekMode, SeekTo};
+use symphonia::core::units::TimeBase;
+
+use super::commons;
+use crate::audio_error::{AudioError, AudioResult};
+
+/// How channels are laid out in a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleLayout {
+    /// Frame after frame, i.e [l, r, l, r]
+    #[default]
+    Interleaved,
+    /// Channel after channel, i.e [l, l, r, r]
+    Planar,
+}
+
+/// Channels mixed into a single one, for mono analysis
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Downmix {
+    Left,
+    /// Left channel for mono files
+    Right,
+    /// Half sum of left & right channels
+    Mid,
+    /// Half difference of left & right channels, silent for mono files
+    Side,
+    /// Mean of every channel, surround ones included
+    #[default]
+    Average,
+}
+
+/// Samples decoded from one packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleBlock<S = f32> {
+    /// Timestamp of the first frame, in track time base units
+    pub ts: u64,
+    /// Time of the first frame, if the time base is known
+    pub time: Option<Duration>,
+    pub rate: u32,
+    pub channels: usize,
+    pub layout: SampleLayout,
+    pub samples: Vec<S>,
+}
+
+impl<S: ConvertibleSample> SampleBlock<S> {
+    pub fn frames(&self) -> usize {
+        self.samples.len() / self.channels.max(1)
+    }
+
+    /// Samples of one channel, whatever the layout
+    pub fn channel(&self, idx: usize) -> impl Iterator<Item = S> + '_ {
+        let frames = self.frames();
+        let (start, step) = match self.layout {
+            SampleLayout::Interleaved => (idx, self.channels.max(1)),
+            SampleLayout::Planar => (idx * frames, 1),
+        };
+        self.samples
+            .iter()
+            .skip(start)
+            .step_by(step)
+            .take(frames)
+            .copied()
+    }
+
+    /// One buffer per channel
+    pub fn to_planar(&self) -> Vec<Vec<S>> {
+        (0..self.channels)
+            .map(|idx| self.channel(idx).collect())
+            .collect()
+    }
+}
+
+impl<S: ConvertibleSample> SampleBlock<S>
+where
+    f64: FromSample<S>,
+{
+    /// Mixes channels into a single one, computed in f64 then converted back
+    pub fn downmix(&self, downmix: Downmix) -> Vec<S> {
+        let channels = self.to_planar();
+        let left = channels.first().map(Vec::as_slice).unwrap_or(&[]);
+        let right = channels.get(1).map(Vec::as_slice).unwrap_or(left);
+        let mix = |sign: f64| {
+            left.iter()
+                .zip(right)
+                .map(|(l, r)| {
+                    S::from_sample((f64::from_sample(*l) + sign * f64::from_sample(*r)) / 2.0)
+                })
+                .collect()
+        };
+        match downmix {
+            Downmix::Left => left.to_vec(),
+            Downmix::Right => right.to_vec(),
+            Downmix::Mid => mix(1.0),
+            Downmix::Side => mix(-1.0),
+            Downmix::Average => (0..self.frames())
+                .map(|frame| {
+                    let sum: f64 = channels
+                        .iter()
+                        .map(|channel| f64::from_sample(channel[frame]))
+                        .sum();
+                    S::from_sample(sum / channels.len() as f64)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Decodes the default track of a file, block after block, into S samples
+/// i.e `SampleStream::<i16>::open(path, SampleLayout::Planar)`.
+/// Iteration stops at the end of the stream, or after the first error
+pub struct SampleStream<S: ConvertibleSample = f32> {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+    layout: SampleLayout,
+    sample_buf: Option<SampleBuffer<S>>,
+    /// Frames before it are decoded as pre-roll, then dropped
+    start_ts: u64,
+    /// Frames from it are dropped, stream stops there
+    end_ts: Option<u64>,
+    done: bool,
+}
+
+impl<S: ConvertibleSample> SampleStream<S> {
+    /// # Errors
+    /// same as commons::get_probe, UnsupportedCodec if the default track can't be decoded
+    pub fn open(audio_path: &Path, layout: SampleLayout) -> AudioResult<SampleStream<S>> {
+        let format = commons::get_probe(audio_path)?.format;
+        let track = format
+            .default_track()
+            .ok_or_else(|| AudioError::UnsupportedFormat("no audio track".to_string()))?;
+        let track_id = track.id;
+        let time_base = track.codec_params.time_base;
+        let decoder = commons::make_decoder(&track.codec_params)?;
+        Ok(SampleStream {
+            format,
+            decoder,
+            track_id,
+            time_base,
+            layout,
+            sample_buf: None,
+            start_ts: 0,
+            end_ts: None,
+            done: false,
+        })
+    }
+
+    /// Stream of frames from `from` to `to` only, each one from the start or end of file if unset.
+    /// The format reader seeks the packet nearest to `from`, so that earlier ones aren't decoded
+    ///
+    /// # Errors
+    /// same as open, Decode if `to` isn't after `from`, if `from` is out of the track or the
+    /// track has no time base
+    pub fn open_range(
+        audio_path: &Path,
+        layout: SampleLayout,
+        from: Option<Duration>,
+        to: Option<Duration>,
+    ) -> AudioResult<SampleStream<S>> {
+        if let Some(to) = to.filter(|to| *to <= from.unwrap_or_default()) {
+            return Err(AudioError::Decode(format!(
+                "empty range, {:?} isn't after {:?}",
+                to,
+                from.unwrap_or_default()
+            )));
+        }
+        let mut stream = SampleStream::open(audio_path, layout)?;
+        if from.is_none() && to.is_none() {
+            return Ok(stream);
+        }
+        let time_base = stream
+            .time_base
+            .ok_or_else(|| AudioError::Decode("unknown time base".to_string()))?;
+        if let Some(from) = from.filter(|from| !from.is_zero()) {
+            let seek_to = SeekTo::TimeStamp {
+                ts: get_timestamp(time_base, from),
+                track_id: stream.track_id,
+            };
+            stream.start_ts = match stream.format.seek(SeekMode::Accurate, seek_to) {
+                Ok(seeked_to) => seeked_to.required_ts,
+                Err(err) if commons::is_end_of_stream(&err) => {
+                    return Err(AudioError::Decode(format!("{:?} is out of track", from)))
+                }
+                Err(err) => return Err(err.into()),
+            };
+            // Decoder state belongs to the packets before the seek.
+            stream.decoder.reset();
+        }
+        stream.end_ts = to.map(|to| get_timestamp(time_base, to));
+        Ok(stream)
+    }
+
+    /// Signal of the decoded track, if its rate & channels are known before decoding
+    pub fn spec(&self) -> Option<SignalSpec> {
+        let params = self.decoder.codec_params();
+        Some(SignalSpec::new(params.sample_rate?, params.channels?))
+    }
+
+    fn next_block(&mut self) -> AudioResult<Option<SampleBlock<S>>> {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(err) if commons::is_end_of_stream(&err) => return Ok(None),
+                Err(err) => return Err(err.into()),
+            };
+            // If the packet does not belong to the selected track, skip it.
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            if self.end_ts.is_some_and(|end_ts| packet.ts() >= end_ts) {
+                return Ok(None);
+            }
+            let audio_buf = self.decoder.decode(&packet)?;
+            let spec = *audio_buf.spec();
+            // Range bounds may be in the middle of this packet, pre-roll ones are fully skipped.
+            let frames = audio_buf.frames() as u64;
+            let skipped = self.start_ts.saturating_sub(packet.ts()).min(frames);
+            let kept = self
+                .end_ts
+                .map_or(frames, |end_ts| frames.min(end_ts - packet.ts()))
+                .saturating_sub(skipped);
+            if kept == 0 {
+                continue;
+            }
+            // Sample buffer is sized on the first packet, a bigger one is reallocated.
+            let sample_buf = match &mut self.sample_buf {
+                Some(buf) if buf.capacity() >= audio_buf.capacity() * spec.channels.count() => buf,
+                sample_buf => {
+                    sample_buf.insert(SampleBuffer::new(audio_buf.capacity() as u64, spec))
+                }
+            };
+            match self.layout {
+                SampleLayout::Interleaved => sample_buf.copy_interleaved_ref(audio_buf),
+                SampleLayout::Planar => sample_buf.copy_planar_ref(audio_buf),
+            }
+            let channels = spec.channels.count();
+            let samples = sample_buf.samples();
+            let samples = if kept == frames {
+                samples.to_vec()
+            } else {
+                let (skipped, kept) = (skipped as usize, kept as usize);
+                match self.layout {
+                    SampleLayout::Interleaved => {
+                        samples[skipped * channels..(skipped + kept) * channels].to_vec()
+                    }
+                    SampleLayout::Planar => samples
+                        .chunks(frames as usize)
+                        .flat_map(|channel| &channel[skipped..skipped + kept])
+                        .copied()
+                        .collect(),
+                }
+            };
+            let ts = packet.ts() + skipped;
+            return Ok(Some(SampleBlock {
+                ts,
+                time: self.time_base.map(|tb| {
+                    let time = tb.calc_time(ts);
+                    Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac)
+                }),
+                rate: spec.rate,
+                channels,
+                layout: self.layout,
+                samples,
+            }));
+        }
+    }
+}
+
+impl<S: ConvertibleSample> Iterator for SampleStream<S> {
+    type Item = AudioResult<SampleBlock<S>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let block = self.next_block();
+        self.done = !matches!(block, Ok(Some(_)));
+        block.transpose()
+    }
+}
+
+/// Timestamp nearest to a time, TimeBase::calc_timestamp flooring i.e 1.2s at 8 kHz into 9599
+fn get_timestamp(time_base: TimeBase, time: Duration) -> u64 {
+    let ticks = time.as_nanos() * u128::from(time_base.denom);
+    let tick_nanos = u128::from(time_base.numer) * 1_000_000_000;
+    ((ticks + tick_nanos / 2) / tick_nanos) as u64
+}
+
+/// Whole file decoded into one buffer per channel
+pub fn get_file_channels<S: ConvertibleSample>(audio_path: &Path) -> AudioResult<Vec<Vec<S>>> {
+    let mut channels: Vec<Vec<S>> = Vec::new();
+    for block in SampleStream::<S>::open(audio_path, SampleLayout::Planar)? {
+        let block = block?;
+        channels.resize_with(block.channels.max(channels.len()), Vec::new);
+        for (idx, channel) in channels.iter_mut().enumerate() {
+            channel.extend(block.channel(idx));
+        }
+    }
+    Ok(channels)
+}
+
+/// Whole file decoded into a single channel
+pub fn get_file_downmix<S: ConvertibleSample>(
+    audio_path: &Path,
+    downmix: Downmix,
+) -> AudioResult<Vec<S>>
+where
+    f64: FromSample<S>,
+{
+    let mut samples = Vec::new();
+    for block in SampleStream::<S>::open(audio_path, SampleLayout::Planar)? {
+        samples.extend(block?.downmix(downmix));
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_ramp;
+
+    fn block(layout: SampleLayout, samples: Vec<f32>) -> SampleBlock {
+        SampleBlock {
+            ts: 0,
+            time: None,
+            rate: 8000,
+            channels: 2,
+            layout,
+            samples,
+        }
+    }
+
+    #[test]
+    fn it_reads_channels_of_both_layouts() {
+        let interleaved = block(SampleLayout::Interleaved, vec![1.0, -1.0, 2.0, -2.0]);
+        let planar = block(SampleLayout::Planar, vec![1.0, 2.0, -1.0, -2.0]);
+        for block in [interleaved, planar] {
+            assert_eq!(block.frames(), 2);
+            assert_eq!(block.channel(0).collect::<Vec<f32>>(), vec![1.0, 2.0]);
+            assert_eq!(block.channel(1).collect::<Vec<f32>>(), vec![-1.0, -2.0]);
+        }
+    }
+
+    #[test]
+    fn it_downmixes_channels() {
+        let stereo = block(SampleLayout::Interleaved, vec![0.5, 0.25, 1.0, -1.0]);
+        assert_eq!(stereo.downmix(Downmix::Left), vec![0.5, 1.0]);
+        assert_eq!(stereo.downmix(Downmix::Right), vec![0.25, -1.0]);
+        assert_eq!(stereo.downmix(Downmix::Mid), vec![0.375, 0.0]);
+        assert_eq!(stereo.downmix(Downmix::Side), vec![0.125, 1.0]);
+        assert_eq!(stereo.downmix(Downmix::Average), vec![0.375, 0.0]);
+        let mono = SampleBlock {
+            channels: 1,
+            samples: vec![0.5, 1.0],
+            ..stereo
+        };
+        assert_eq!(mono.downmix(Downmix::Right), vec![0.5, 1.0]);
+        assert_eq!(mono.downmix(Downmix::Side), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_streams_wav_file() {
+        let path = write_ramp("stream.wav", 10_000);
+
+        let blocks: Vec<SampleBlock> = SampleStream::open(&path, SampleLayout::Planar)
+            .unwrap()
+            .collect::<AudioResult<Vec<SampleBlock>>>()
+            .unwrap();
+        assert!(blocks.len() > 1);
+        assert_eq!(
+            blocks.iter().map(SampleBlock::frames).sum::<usize>(),
+            10_000
+        );
+        let last = blocks.last().unwrap();
+        let last_time = last.time.unwrap().as_secs_f64();
+        assert!((last_time - last.ts as f64 / 8000.0).abs() < 1e-6);
+        // Right channel is the opposite of the left one.
+        assert!(blocks
+            .iter()
+            .all(|block| block.channel(0).zip(block.channel(1)).all(|(l, r)| l == -r)));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_reads_file_as_integers() {
+        let path = write_ramp("integers.wav", 10_000);
+
+        let channels = get_file_channels::<i16>(&path).unwrap();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0][..3], [0, 1, 2]);
+        assert_eq!(channels[1][99], -99);
+        // 16 bits samples are scaled to 32 bits ones.
+        let side = get_file_downmix::<i32>(&path, Downmix::Side).unwrap();
+        assert_eq!(side.len(), 10_000);
+        assert_eq!(side[1], 1 << 16);
+        let mid = get_file_downmix::<f64>(&path, Downmix::Mid).unwrap();
+        assert!(mid.iter().all(|sample| *sample == 0.0));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_reads_time_range() {
+        let path = write_ramp("range.wav", 10_000);
+
+        let blocks: Vec<SampleBlock<i16>> = SampleStream::open_range(
+            &path,
+            SampleLayout::Planar,
+            Some(Duration::from_millis(500)),
+            Some(Duration::from_millis(750)),
+        )
+        .unwrap()
+        .collect::<AudioResult<Vec<SampleBlock<i16>>>>()
+        .unwrap();
+        assert_eq!(blocks[0].ts, 4000);
+        assert_eq!(blocks[0].time, Some(Duration::from_millis(500)));
+        // Frames are those of the range only, pre-roll and tail trimmed.
+        let left: Vec<i16> = blocks.iter().flat_map(|block| block.channel(0)).collect();
+        let right: Vec<i16> = blocks.iter().flat_map(|block| block.channel(1)).collect();
+        assert_eq!(left, (4000..6000).collect::<Vec<i16>>());
+        assert_eq!(
+            right,
+            (4000..6000).map(|frame| -frame).collect::<Vec<i16>>()
+        );
+
+        let tail = SampleStream::<f32>::open_range(
+            &path,
+            SampleLayout::Interleaved,
+            Some(Duration::from_millis(1200)),
+            None,
+        )
+        .unwrap()
+        .map(|block| block.unwrap().frames())
+        .sum::<usize>();
+        assert_eq!(tail, 400);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_rejects_empty_time_range() {
+        let path = write_ramp("empty-range.wav", 10_000);
+
+        for (from, to) in [(750, 500), (500, 500), (0, 0)] {
+            let stream = SampleStream::<f32>::open_range(
+                &path,
+                SampleLayout::Planar,
+                Some(Duration::from_millis(from)),
+                Some(Duration::from_millis(to)),
+            );
+            assert!(matches!(stream, Err(AudioError::Decode(_))));
+        }
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,292 @@
+//!
+//! Decoded audio written into WAV files, for tools reading WAV only i.e hound based beat detection.
+//!
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use symphonia::core::audio::{AudioBufferRef, Signal};
+use symphonia::core::conv::FromSample;
+use symphonia::core::sample::i24;
+
+use super::resampler::{ResampleQuality, Resampler};
+use super::sample_stream::{SampleLayout, SampleStream};
+use crate::audio_error::{AudioError, AudioResult};
+
+/// Frames handed to the resampler at once
+const RESAMPLE_CHUNK: usize = 1024;
+
+type WavWriter = hound::WavWriter<BufWriter<File>>;
+
+/// Sample format of the WAV file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum WavBitDepth {
+    /// 16 bits integers, read by most tools
+    #[default]
+    #[value(name = "16")]
+    Int16,
+    /// 24 bits integers
+    #[value(name = "24")]
+    Int24,
+    /// 32 bits floats, decoded samples are kept as is
+    #[value(name = "32f")]
+    Float32,
+}
+
+impl WavBitDepth {
+    fn wav_spec(&self, channels: u16, sample_rate: u32) -> hound::WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            WavBitDepth::Int16 => (16, hound::SampleFormat::Int),
+            WavBitDepth::Int24 => (24, hound::SampleFormat::Int),
+            WavBitDepth::Float32 => (32, hound::SampleFormat::Float),
+        };
+        hound::WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WavExportOptions {
+    pub bit_depth: WavBitDepth,
+    /// Rate of the WAV file, audio one if not set
+    pub sample_rate: Option<u32>,
+    pub resample_quality: ResampleQuality,
+    /// Time to export from, start of file if not set
+    pub from: Option<Duration>,
+    /// Time to export to, end of file if not set
+    pub to: Option<Duration>,
+}
+
+/// Decodes any supported audio file into a WAV file. Channels are kept in the decoder order,
+/// which is the WAV one i.e front left, front right, center, LFE...
+///
+/// # Returns
+/// frames count written
+///
+/// # Errors
+/// same as SampleStream::open_range, Io if the WAV file can't be written
+pub fn export_wav(
+    audio_path: &Path,
+    wav_path: &Path,
+    options: &WavExportOptions,
+) -> AudioResult<u64> {
+    let stream = SampleStream::<f32>::open_range(
+        audio_path,
+        SampleLayout::Planar,
+        options.from,
+        options.to,
+    )?;
+    let spec = stream.spec().ok_or_else(|| {
+        AudioError::UnsupportedFormat("unknown sample rate or channels".to_string())
+    })?;
+    let sample_rate = options.sample_rate.unwrap_or(spec.rate);
+    let wav_spec = options
+        .bit_depth
+        .wav_spec(spec.channels.count() as u16, sample_rate);
+    let mut writer = hound::WavWriter::create(wav_path, wav_spec)?;
+    let mut resampler = (sample_rate != spec.rate).then(|| {
+        Resampler::new(
+            spec,
+            sample_rate,
+            RESAMPLE_CHUNK as u64,
+            options.resample_quality,
+        )
+    });
+
+    // Resampler output lags behind its input, leading frames are dropped to keep the audio timing.
+    let mut delay = resampler.as_ref().map_or(0, Resampler::output_delay);
+    let mut frames = 0;
+    let mut input_frames = 0;
+    for block in stream {
+        let planes = block?.to_planar();
+        let len = planes.first().map_or(0, Vec::len);
+        input_frames += len as u64;
+        let Some(resampler) = resampler.as_mut() else {
+            frames += write_planes(&mut writer, options.bit_depth, &planes, len)?;
+            continue;
+        };
+        // Chunks fit the resampler one, so that pending frames don't pile up.
+        for start in (0..len).step_by(RESAMPLE_CHUNK) {
+            let end = (start + RESAMPLE_CHUNK).min(len);
+            let chunk: Vec<Vec<f32>> = planes
+                .iter()
+                .map(|plane| plane[start..end].to_vec())
+                .collect();
+            if let Some(resampled) = resampler.resample_planes(&chunk) {
+                frames += write_resampled(
+                    &mut writer,
+                    options.bit_depth,
+                    resampled,
+                    &mut delay,
+                    u64::MAX,
+                )?;
+            }
+        }
+    }
+    // Last chunk is padded with silence, more of it pushes delayed frames out. Silence is dropped
+    // past the input duration.
+    let expected =
+        (input_frames * u64::from(sample_rate) + u64::from(spec.rate / 2)) / u64::from(spec.rate);
+    if let Some(resampler) = resampler.as_mut() {
+        if let Some(resampled) = resampler.flush() {
+            let remaining = expected.saturating_sub(frames);
+            frames += write_resampled(
+                &mut writer,
+                options.bit_depth,
+                resampled,
+                &mut delay,
+                remaining,
+            )?;
+        }
+        let silence = vec![vec![0.0; RESAMPLE_CHUNK]; spec.channels.count()];
+        while frames < expected {
+            if let Some(resampled) = resampler.resample_planes(&silence) {
+                let remaining = expected - frames;
+                frames += write_resampled(
+                    &mut writer,
+                    options.bit_depth,
+                    resampled,
+                    &mut delay,
+                    remaining,
+                )?;
+            }
+        }
+    }
+    writer.finalize()?;
+    Ok(frames)
+}
+
+/// Writes resampled frames, once the first `delay` ones are dropped
+fn write_resampled(
+    writer: &mut WavWriter,
+    bit_depth: WavBitDepth,
+    resampled: AudioBufferRef<'_>,
+    delay: &mut usize,
+    max_frames: u64,
+) -> AudioResult<u64> {
+    let AudioBufferRef::F32(buf) = resampled else {
+        unreachable!("resampler output is f32")
+    };
+    let skipped = (*delay).min(buf.frames());
+    *delay -= skipped;
+    let frames = ((buf.frames() - skipped) as u64).min(max_frames) as usize;
+    let planes: Vec<&[f32]> = buf
+        .planes()
+        .planes()
+        .iter()
+        .map(|plane| &plane[skipped..])
+        .collect();
+    write_planes(writer, bit_depth, &planes, frames)
+}
+
+/// Interleaves the first frames of planar samples into the WAV file
+fn write_planes<P: AsRef<[f32]>>(
+    writer: &mut WavWriter,
+    bit_depth: WavBitDepth,
+    planes: &[P],
+    frames: usize,
+) -> AudioResult<u64> {
+    for frame in 0..frames {
+        for plane in planes {
+            let sample = plane.as_ref()[frame];
+            match bit_depth {
+                WavBitDepth::Int16 => writer.write_sample(i16::from_sample(sample))?,
+                WavBitDepth::Int24 => writer.write_sample(i24::from_sample(sample).inner())?,
+                WavBitDepth::Float32 => writer.write_sample(sample)?,
+            }
+        }
+    }
+    Ok(frames as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{temp_path, write_ramp};
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_exports_time_range_as_24_bits() {
+        let input = write_ramp("export-input.wav", 8000);
+        let output = temp_path("export-24.wav");
+
+        let options = WavExportOptions {
+            bit_depth: WavBitDepth::Int24,
+            from: Some(Duration::from_millis(250)),
+            to: Some(Duration::from_millis(500)),
+            ..Default::default()
+        };
+        assert_eq!(export_wav(&input, &output, &options).unwrap(), 2000);
+        let mut reader = hound::WavReader::open(&output).unwrap();
+        assert_eq!(reader.spec(), WavBitDepth::Int24.wav_spec(2, 8000));
+        let samples: Vec<i32> = reader.samples::<i32>().map(Result::unwrap).collect();
+        // 16 bits samples are scaled to 24 bits ones.
+        assert_eq!(samples[..4], [2000 << 8, -2000 << 8, 2001 << 8, -2001 << 8]);
+        assert_eq!(samples.len(), 4000);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_exports_resampled_file() {
+        let input = write_ramp("resample-input.wav", 8000);
+        let output = temp_path("resample-32f.wav");
+
+        let options = WavExportOptions {
+            bit_depth: WavBitDepth::Float32,
+            sample_rate: Some(16000),
+            ..Default::default()
+        };
+        let frames = export_wav(&input, &output, &options).unwrap();
+        assert_eq!(frames, 16000);
+        let reader = hound::WavReader::open(&output).unwrap();
+        assert_eq!(reader.spec(), WavBitDepth::Float32.wav_spec(2, 16000));
+        assert_eq!(u64::from(reader.duration()), frames);
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+
+    #[test]
+    #[cfg_attr(not(feature = "wav"), ignore = "needs wav feature")]
+    fn it_trims_resampler_delay() {
+        let input = write_ramp("delay-input.wav", 8000);
+        let output = temp_path("delay-32f.wav");
+
+        for resample_quality in [
+            ResampleQuality::Fast,
+            ResampleQuality::Balanced,
+            ResampleQuality::Best,
+        ] {
+            let options = WavExportOptions {
+                bit_depth: WavBitDepth::Float32,
+                sample_rate: Some(16000),
+                resample_quality,
+                ..Default::default()
+            };
+            assert_eq!(export_wav(&input, &output, &options).unwrap(), 16000);
+            let mut reader = hound::WavReader::open(&output).unwrap();
+            let samples: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+            // Left ramp keeps its timing, twice as many frames per second.
+            let left = samples[2 * 8000] * 32768.0;
+            assert!(
+                (left - 4000.0).abs() < 2.0,
+                "{:?}: {}",
+                resample_quality,
+                left
+            );
+        }
+
+        let _ = std::fs::remove_file(input);
+        let _ = std::fs::remove_file(output);
+    }
+}
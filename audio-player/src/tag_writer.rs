@@ -0,0 +1,626 @@
+//!
+//! Tag writing, symphonia being read-only: ID3v2.3/2.4 tags of MP3 files and Vorbis comments
+//! of FLAC files. Audio data is copied as is, only tags are rewritten.
+//!
+
+use id3::frame::{Comment, ExtendedText, Lyrics, UniqueFileIdentifier};
+use id3::{TagLike, Version};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::audio_format::{self, AudioFormat};
+use crate::{AudioError, AudioResult, AudioTag};
+
+/// FLAC metadata block types
+const STREAMINFO: u8 = 0;
+const PADDING: u8 = 1;
+const VORBIS_COMMENT: u8 = 4;
+/// Block lengths are written on 24 bits
+const MAX_BLOCK_LEN: usize = 0xFF_FFFF;
+/// Padding added when the file is rewritten, so that next edits fit in place
+const DEFAULT_PADDING: usize = 4096;
+const VENDOR: &str = "audio-player";
+/// UFID owner of MusicBrainz recording identifiers
+const MUSICBRAINZ_OWNER: &str = "http://musicbrainz.org";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagEdit {
+    /// Replaces every value of a tag
+    Set(AudioTag, String),
+    Remove(AudioTag),
+}
+
+impl TagEdit {
+    /// Parses "genre=Jazz", tag names being the ones of AudioTag::from.
+    /// Read only tags and invalid values are rejected, as write_tags does
+    pub fn parse_set(arg: &str) -> Result<TagEdit, String> {
+        let (key, value) = arg
+            .split_once('=')
+            .ok_or_else(|| format!("expected <TAG>=<VALUE>, got {}", arg))?;
+        let edit = TagEdit::Set(parse_tag(key)?, value.to_string());
+        check_edit(&edit).map_err(|err| err.to_string())?;
+        Ok(edit)
+    }
+
+    /// Parses "genre"
+    pub fn parse_remove(arg: &str) -> Result<TagEdit, String> {
+        let edit = TagEdit::Remove(parse_tag(arg)?);
+        check_edit(&edit).map_err(|err| err.to_string())?;
+        Ok(edit)
+    }
+
+    pub fn tag(&self) -> &AudioTag {
+        match self {
+            TagEdit::Set(tag, _) | TagEdit::Remove(tag) => tag,
+        }
+    }
+
+    fn value(&self) -> Option<&str> {
+        match self {
+            TagEdit::Set(_, value) => Some(value),
+            TagEdit::Remove(_) => None,
+        }
+    }
+}
+
+fn parse_tag(key: &str) -> Result<AudioTag, String> {
+    match AudioTag::from(key) {
+        AudioTag::Unknown => Err(format!("unknown tag {}", key)),
+        tag => Ok(tag),
+    }
+}
+
+/// Applies tag edits in the given order, a file is written once
+///
+/// # Errors
+/// UnsupportedFormat for other formats than MP3 & FLAC, UnwritableTag for metadata computed
+/// from the audio stream i.e duration, or a non numeric track / disc number
+pub fn write_tags(audio_path: &Path, edits: &[TagEdit]) -> AudioResult<()> {
+    match check_tags(audio_path, edits)? {
+        AudioFormat::Mp3 => write_id3(audio_path, edits),
+        _ => write_vorbis_comments(audio_path, edits),
+    }
+}
+
+/// Checks tag edits could be written without writing them, i.e for a dry run
+///
+/// # Returns
+/// MP3 or FLAC, the file format
+///
+/// # Errors
+/// same as write_tags
+pub fn check_tags(audio_path: &Path, edits: &[TagEdit]) -> AudioResult<AudioFormat> {
+    for edit in edits {
+        check_edit(edit)?;
+    }
+    match audio_format::detect_format(audio_path)?.format() {
+        Some(format @ (AudioFormat::Mp3 | AudioFormat::Flac)) => Ok(format),
+        Some(format) => Err(AudioError::UnsupportedFormat(format!(
+            "tag writing for {}",
+            format
+        ))),
+        None => Err(AudioError::NoExtension),
+    }
+}
+
+fn check_edit(edit: &TagEdit) -> AudioResult<()> {
+    let tag = edit.tag();
+    if vorbis_keys(tag).is_empty() {
+        return Err(AudioError::UnwritableTag(format!("{:?} is read only", tag)));
+    }
+    if let (Some(value), true) = (edit.value(), is_number_tag(tag)) {
+        parse_number(tag, value)?;
+    }
+    Ok(())
+}
+
+fn is_number_tag(tag: &AudioTag) -> bool {
+    matches!(
+        tag,
+        AudioTag::TrackNumber | AudioTag::TrackTotal | AudioTag::DiscNumber | AudioTag::DiscTotal
+    )
+}
+
+fn parse_number(tag: &AudioTag, value: &str) -> AudioResult<u32> {
+    value.trim().parse().map_err(|_| {
+        AudioError::UnwritableTag(format!("{:?} expects a number, got {}", tag, value))
+    })
+}
+
+/// Vorbis comment field names, the first one is written, all are replaced
+fn vorbis_keys(tag: &AudioTag) -> &'static [&'static str] {
+    match tag {
+        AudioTag::Artist => &["ARTIST"],
+        AudioTag::Album => &["ALBUM"],
+        AudioTag::AlbumArtist => &["ALBUMARTIST", "ALBUM ARTIST"],
+        AudioTag::Composer => &["COMPOSER"],
+        AudioTag::Conductor => &["CONDUCTOR"],
+        AudioTag::Bpm => &["BPM"],
+        AudioTag::Date => &["DATE", "YEAR"],
+        AudioTag::Genre => &["GENRE"],
+        AudioTag::Lyrics => &["LYRICS", "UNSYNCEDLYRICS"],
+        AudioTag::TrackNumber => &["TRACKNUMBER"],
+        AudioTag::TrackTotal => &["TRACKTOTAL", "TOTALTRACKS"],
+        AudioTag::TrackName => &["TITLE"],
+        AudioTag::DiscNumber => &["DISCNUMBER", "DISC", "DISK", "DISKNUMBER"],
+        AudioTag::DiscTotal => &["DISCTOTAL", "TOTALDISCS", "DISKTOTAL"],
+        AudioTag::Comment => &["COMMENT"],
+        AudioTag::Isrc => &["ISRC"],
+        AudioTag::Label => &["LABEL", "ORGANIZATION", "PUBLISHER"],
+        AudioTag::Compilation => &["COMPILATION"],
+        AudioTag::MusicBrainzRecordingId => &["MUSICBRAINZ_RECORDINGID"],
+        AudioTag::MusicBrainzReleaseId => &["MUSICBRAINZ_ALBUMID"],
+        AudioTag::MusicBrainzArtistId => &["MUSICBRAINZ_ARTISTID"],
+        AudioTag::ReplayGainTrackGain => &["REPLAYGAIN_TRACK_GAIN"],
+        AudioTag::ReplayGainTrackPeak => &["REPLAYGAIN_TRACK_PEAK"],
+        AudioTag::ReplayGainAlbumGain => &["REPLAYGAIN_ALBUM_GAIN"],
+        AudioTag::ReplayGainAlbumPeak => &["REPLAYGAIN_ALBUM_PEAK"],
+        _ => &[],
+    }
+}
+
+/// ID3v2 text frames, tags stored in other frames are handled by apply_id3_edit
+fn id3_text_frame(tag: &AudioTag) -> Option<&'static str> {
+    match tag {
+        AudioTag::Artist => Some("TPE1"),
+        AudioTag::Album => Some("TALB"),
+        AudioTag::AlbumArtist => Some("TPE2"),
+        AudioTag::Composer => Some("TCOM"),
+        AudioTag::Conductor => Some("TPE3"),
+        AudioTag::Bpm => Some("TBPM"),
+        AudioTag::Genre => Some("TCON"),
+        AudioTag::TrackName => Some("TIT2"),
+        AudioTag::Isrc => Some("TSRC"),
+        AudioTag::Label => Some("TPUB"),
+        AudioTag::Compilation => Some("TCMP"),
+        _ => None,
+    }
+}
+
+/// ID3v2 user defined text descriptions, as symphonia reads them
+fn id3_extended_text(tag: &AudioTag) -> Option<&'static str> {
+    match tag {
+        AudioTag::MusicBrainzReleaseId => Some("MUSICBRAINZ ALBUM ID"),
+        AudioTag::MusicBrainzArtistId => Some("MUSICBRAINZ ARTIST ID"),
+        AudioTag::ReplayGainTrackGain => Some("REPLAYGAIN_TRACK_GAIN"),
+        AudioTag::ReplayGainTrackPeak => Some("REPLAYGAIN_TRACK_PEAK"),
+        AudioTag::ReplayGainAlbumGain => Some("REPLAYGAIN_ALBUM_GAIN"),
+        AudioTag::ReplayGainAlbumPeak => Some("REPLAYGAIN_ALBUM_PEAK"),
+        _ => None,
+    }
+}
+
+fn write_id3(audio_path: &Path, edits: &[TagEdit]) -> AudioResult<()> {
+    let mut tag = id3::no_tag_ok(id3::Tag::read_from_path(audio_path))?.unwrap_or_default();
+    // ID3v2.2 can't be written, such tags are upgraded.
+    let version = match tag.version() {
+        Version::Id3v23 => Version::Id3v23,
+        _ => Version::Id3v24,
+    };
+    for edit in edits {
+        apply_id3_edit(&mut tag, edit, version)?;
+    }
+    Ok(tag.write_to_path(audio_path, version)?)
+}
+
+fn apply_id3_edit(tag: &mut id3::Tag, edit: &TagEdit, version: Version) -> AudioResult<()> {
+    let audio_tag = edit.tag();
+    let value = edit.value();
+    match audio_tag {
+        AudioTag::TrackNumber => match value {
+            Some(value) => tag.set_track(parse_number(audio_tag, value)?),
+            None => tag.remove_track(),
+        },
+        AudioTag::TrackTotal => match value {
+            Some(value) => tag.set_total_tracks(parse_number(audio_tag, value)?),
+            None => tag.remove_total_tracks(),
+        },
+        AudioTag::DiscNumber => match value {
+            Some(value) => tag.set_disc(parse_number(audio_tag, value)?),
+            None => tag.remove_disc(),
+        },
+        AudioTag::DiscTotal => match value {
+            Some(value) => tag.set_total_discs(parse_number(audio_tag, value)?),
+            None => tag.remove_total_discs(),
+        },
+        // ID3v2.3 has no recording time frame, only a year one.
+        AudioTag::Date => {
+            tag.remove("TDRC");
+            tag.remove("TYER");
+            if let Some(value) = value {
+                let frame_id = match version {
+                    Version::Id3v23 => "TYER",
+                    _ => "TDRC",
+                };
+                tag.set_text(frame_id, value);
+            }
+        }
+        // Comments & lyrics with a description are left as is, i.e iTunes normalization.
+        AudioTag::Comment => {
+            tag.remove_comment(Some(""), None);
+            if let Some(value) = value {
+                tag.add_frame(Comment {
+                    lang: "eng".to_string(),
+                    description: String::new(),
+                    text: value.to_string(),
+                });
+            }
+        }
+        AudioTag::Lyrics => {
+            tag.remove_all_lyrics();
+            if let Some(value) = value {
+                tag.add_frame(Lyrics {
+                    lang: "eng".to_string(),
+                    description: String::new(),
+                    text: value.to_string(),
+                });
+            }
+        }
+        AudioTag::MusicBrainzRecordingId => {
+            tag.remove_unique_file_identifier_by_owner_identifier(MUSICBRAINZ_OWNER);
+            if let Some(value) = value {
+                tag.add_frame(UniqueFileIdentifier {
+                    owner_identifier: MUSICBRAINZ_OWNER.to_string(),
+                    identifier: value.as_bytes().to_vec(),
+                });
+            }
+        }
+        _ => {
+            if let Some(frame_id) = id3_text_frame(audio_tag) {
+                match value {
+                    Some(value) => tag.set_text(frame_id, value),
+                    None => {
+                        tag.remove(frame_id);
+                    }
+                }
+            } else if let Some(description) = id3_extended_text(audio_tag) {
+                // Taggers don't agree on the description case, i.e "MusicBrainz Album Id".
+                let previous: Vec<String> = tag
+                    .extended_texts()
+                    .filter(|text| text.description.eq_ignore_ascii_case(description))
+                    .map(|text| text.description.clone())
+                    .collect();
+                for previous in previous {
+                    tag.remove_extended_text(Some(&previous), None);
+                }
+                if let Some(value) = value {
+                    tag.add_frame(ExtendedText {
+                        description: description.to_string(),
+                        value: value.to_string(),
+                    });
+                }
+            } else {
+                return Err(AudioError::UnwritableTag(format!(
+                    "{:?} has no ID3v2 frame",
+                    audio_tag
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+struct MetadataBlock {
+    block_type: u8,
+    data: Vec<u8>,
+}
+
+/// Rewrites the VORBIS_COMMENT block. Audio frames are written in place when the padding
+/// absorbs the size change, the file is rewritten otherwise
+fn write_vorbis_comments(audio_path: &Path, edits: &[TagEdit]) -> AudioResult<()> {
+    let mut file = File::open(audio_path)?;
+    let mut blocks = read_flac_blocks(&mut file)?;
+    let audio_offset = file.stream_position()?;
+
+    let comments_idx = blocks
+        .iter()
+        .position(|block| block.block_type == VORBIS_COMMENT);
+    let (vendor, mut comments) = match comments_idx {
+        Some(idx) => parse_vorbis_comments(&blocks[idx].data)?,
+        None => (VENDOR.to_string(), Vec::new()),
+    };
+    for edit in edits {
+        apply_vorbis_edit(&mut comments, edit);
+    }
+    let data = encode_vorbis_comments(&vendor, &comments);
+    match comments_idx {
+        Some(idx) => blocks[idx].data = data,
+        // STREAMINFO must stay the first block.
+        None => blocks.insert(
+            1,
+            MetadataBlock {
+                block_type: VORBIS_COMMENT,
+                data,
+            },
+        ),
+    }
+
+    if fit_padding(&mut blocks, audio_offset) {
+        drop(file);
+        let mut file = OpenOptions::new().write(true).open(audio_path)?;
+        file.write_all(&encode_flac_blocks(&blocks)?)?;
+        return Ok(());
+    }
+    if !blocks.iter().any(|block| block.block_type == PADDING) {
+        blocks.push(MetadataBlock {
+            block_type: PADDING,
+            data: vec![0; DEFAULT_PADDING],
+        });
+    }
+    // A temporary file keeps the original one safe if writing fails.
+    let temp_path = get_temp_path(audio_path);
+    let mut temp_file = File::create(&temp_path)?;
+    temp_file.write_all(&encode_flac_blocks(&blocks)?)?;
+    file.seek(SeekFrom::Start(audio_offset))?;
+    io::copy(&mut file, &mut temp_file)?;
+    temp_file.sync_all()?;
+    drop(file);
+    fs::rename(&temp_path, audio_path)?;
+    Ok(())
+}
+
+fn read_flac_blocks<R: Read>(reader: &mut R) -> AudioResult<Vec<MetadataBlock>> {
+    let mut marker = [0u8; 4];
+    reader.read_exact(&mut marker)?;
+    if &marker != b"fLaC" {
+        return Err(AudioError::UnsupportedFormat(
+            "FLAC stream doesn't start the file".to_string(),
+        ));
+    }
+    let mut blocks = Vec::new();
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header)?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let mut data = vec![0; len];
+        reader.read_exact(&mut data)?;
+        blocks.push(MetadataBlock {
+            block_type: header[0] & 0x7F,
+            data,
+        });
+        if header[0] & 0x80 != 0 {
+            break;
+        }
+    }
+    if blocks.first().map(|block| block.block_type) != Some(STREAMINFO) {
+        return Err(AudioError::Decode(
+            "FLAC stream has no STREAMINFO".to_string(),
+        ));
+    }
+    Ok(blocks)
+}
+
+/// Resizes the padding so that audio frames keep their offset
+///
+/// # Returns
+/// true if audio frames are at the same offset
+fn fit_padding(blocks: &mut [MetadataBlock], audio_offset: u64) -> bool {
+    let metadata_len = 4 + blocks
+        .iter()
+        .map(|block| 4 + block.data.len() as u64)
+        .sum::<u64>();
+    let Some(padding) = blocks.iter_mut().find(|block| block.block_type == PADDING) else {
+        return metadata_len == audio_offset;
+    };
+    let padding_len = padding.data.len() as u64 + audio_offset;
+    match padding_len.checked_sub(metadata_len) {
+        Some(len) if len <= MAX_BLOCK_LEN as u64 => {
+            padding.data.resize(len as usize, 0);
+            true
+        }
+        _ => false,
+    }
+}
+
+fn encode_flac_blocks(blocks: &[MetadataBlock]) -> AudioResult<Vec<u8>> {
+    let mut bytes = b"fLaC".to_vec();
+    for (idx, block) in blocks.iter().enumerate() {
+        if block.data.len() > MAX_BLOCK_LEN {
+            return Err(AudioError::UnwritableTag(format!(
+                "FLAC metadata block of {} bytes",
+                block.data.len()
+            )));
+        }
+        let last = if idx + 1 == blocks.len() { 0x80 } else { 0 };
+        let len = (block.data.len() as u32).to_be_bytes();
+        bytes.extend_from_slice(&[last | block.block_type, len[1], len[2], len[3]]);
+        bytes.extend_from_slice(&block.data);
+    }
+    Ok(bytes)
+}
+
+/// # Returns
+/// (vendor, "KEY=value" comments)
+fn parse_vorbis_comments(data: &[u8]) -> AudioResult<(String, Vec<String>)> {
+    let invalid = || AudioError::Decode("malformed Vorbis comment block".to_string());
+    let mut reader = data;
+    let vendor = read_vorbis_string(&mut reader).ok_or_else(invalid)?;
+    let mut count = [0u8; 4];
+    reader.read_exact(&mut count).map_err(|_| invalid())?;
+    let comments = (0..u32::from_le_bytes(count))
+        .map(|_| read_vorbis_string(&mut reader).ok_or_else(invalid))
+        .collect::<AudioResult<Vec<String>>>()?;
+    Ok((vendor, comments))
+}
+
+fn encode_vorbis_comments(vendor: &str, comments: &[String]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    push_vorbis_string(&mut bytes, vendor);
+    bytes.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        push_vorbis_string(&mut bytes, comment);
+    }
+    bytes
+}
+
+/// Strings are prefixed by their little endian length
+fn push_vorbis_string(bytes: &mut Vec<u8>, string: &str) {
+    bytes.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+fn read_vorbis_string(reader: &mut &[u8]) -> Option<String> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len).ok()?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > reader.len() {
+        return None;
+    }
+    let (string, rest) = reader.split_at(len);
+    *reader = rest;
+    Some(String::from_utf8_lossy(string).into_owned())
+}
+
+/// Field names are case insensitive
+fn apply_vorbis_edit(comments: &mut Vec<String>, edit: &TagEdit) {
+    let keys = vorbis_keys(edit.tag());
+    comments.retain(|comment| {
+        let key = comment.split('=').next().unwrap_or("");
+        !keys.iter().any(|known| known.eq_ignore_ascii_case(key))
+    });
+    if let Some(value) = edit.value() {
+        comments.push(format!("{}={}", keys[0], value));
+    }
+}
+
+fn get_temp_path(audio_path: &Path) -> PathBuf {
+    let mut file_name = audio_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tags-tmp");
+    audio_path.with_file_name(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    /// Metadata blocks, then fake audio frames
+    fn build_flac(blocks: &[MetadataBlock]) -> (Vec<u8>, Vec<u8>) {
+        let audio = (0..=255u8).cycle().take(1000).collect::<Vec<u8>>();
+        let mut bytes = encode_flac_blocks(blocks).unwrap();
+        bytes.extend_from_slice(&audio);
+        (bytes, audio)
+    }
+
+    /// STREAMINFO of a 44.1kHz stereo 16 bits stream
+    fn streaminfo() -> MetadataBlock {
+        let mut data = vec![0u8; 34];
+        data[10..14].copy_from_slice(&[0x0A, 0xC4, 0x42, 0xF0]);
+        MetadataBlock {
+            block_type: STREAMINFO,
+            data,
+        }
+    }
+
+    #[test]
+    fn it_parses_edits() {
+        assert_eq!(
+            TagEdit::parse_set("genre=Jazz=Funk"),
+            Ok(TagEdit::Set(AudioTag::Genre, "Jazz=Funk".to_string()))
+        );
+        assert_eq!(
+            TagEdit::parse_remove("comment"),
+            Ok(TagEdit::Remove(AudioTag::Comment))
+        );
+        assert!(TagEdit::parse_set("genre").is_err());
+        assert!(TagEdit::parse_remove("mood").is_err());
+        assert!(TagEdit::parse_remove("duration").is_err());
+        assert!(TagEdit::parse_set("trackNumber=A1").is_err());
+    }
+
+    #[test]
+    fn it_edits_vorbis_comments() {
+        let mut comments = vec!["genre=Rock".to_string(), "TITLE=Roygbiv".to_string()];
+        apply_vorbis_edit(
+            &mut comments,
+            &TagEdit::Set(AudioTag::Genre, "Jazz".to_string()),
+        );
+        apply_vorbis_edit(&mut comments, &TagEdit::Remove(AudioTag::TrackName));
+        assert_eq!(comments, vec!["GENRE=Jazz".to_string()]);
+
+        let data = encode_vorbis_comments("vendor", &comments);
+        assert_eq!(
+            parse_vorbis_comments(&data).unwrap(),
+            ("vendor".to_string(), comments)
+        );
+    }
+
+    #[test]
+    fn it_writes_flac_tags_in_padding() {
+        let path = temp_path("padded.flac");
+        let padding = MetadataBlock {
+            block_type: PADDING,
+            data: vec![0; 100],
+        };
+        let (bytes, audio) = build_flac(&[streaminfo(), padding]);
+        fs::write(&path, &bytes).unwrap();
+
+        let edits = [TagEdit::Set(AudioTag::Genre, "Jazz".to_string())];
+        write_tags(&path, &edits).unwrap();
+        let written = fs::read(&path).unwrap();
+        // Audio frames keep their offset & content.
+        assert_eq!(written.len(), bytes.len());
+        assert!(written.ends_with(&audio));
+        let blocks = read_flac_blocks(&mut written.as_slice()).unwrap();
+        let (vendor, comments) = parse_vorbis_comments(&blocks[1].data).unwrap();
+        assert_eq!(vendor, VENDOR);
+        assert_eq!(comments, vec!["GENRE=Jazz".to_string()]);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn it_rewrites_flac_without_padding() {
+        let path = temp_path("unpadded.flac");
+        let (bytes, audio) = build_flac(&[streaminfo()]);
+        fs::write(&path, &bytes).unwrap();
+
+        let edits = [
+            TagEdit::Set(AudioTag::Artist, "Boards of Canada".to_string()),
+            TagEdit::Set(AudioTag::TrackNumber, "3".to_string()),
+        ];
+        write_tags(&path, &edits).unwrap();
+        let written = fs::read(&path).unwrap();
+        assert!(written.ends_with(&audio));
+        let blocks = read_flac_blocks(&mut written.as_slice()).unwrap();
+        assert_eq!(blocks[1].block_type, VORBIS_COMMENT);
+        assert_eq!(blocks[2].data.len(), DEFAULT_PADDING);
+        assert!(!get_temp_path(&path).exists());
+
+        write_tags(&path, &[TagEdit::Remove(AudioTag::Artist)]).unwrap();
+        let blocks = read_flac_blocks(&mut fs::read(&path).unwrap().as_slice()).unwrap();
+        let (_, comments) = parse_vorbis_comments(&blocks[1].data).unwrap();
+        assert_eq!(comments, vec!["TRACKNUMBER=3".to_string()]);
+
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn it_writes_id3_tags() {
+        let path = temp_path("tagged.mp3");
+        // MPEG-1 layer III frame header, then frame content
+        let mut audio = vec![0xFF, 0xFB, 0x90, 0x64];
+        audio.resize(417, 0x55);
+        fs::write(&path, &audio).unwrap();
+
+        let edits = [
+            TagEdit::Set(AudioTag::Genre, "Jazz".to_string()),
+            TagEdit::Set(AudioTag::TrackNumber, "2".to_string()),
+            TagEdit::Set(AudioTag::ReplayGainTrackGain, "-6.50 dB".to_string()),
+        ];
+        write_tags(&path, &edits).unwrap();
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.genre(), Some("Jazz"));
+        assert_eq!(tag.track(), Some(2));
+        assert_eq!(tag.extended_texts().count(), 1);
+        assert!(fs::read(&path).unwrap().ends_with(&audio));
+
+        write_tags(&path, &[TagEdit::Remove(AudioTag::Genre)]).unwrap();
+        let tag = id3::Tag::read_from_path(&path).unwrap();
+        assert_eq!(tag.genre(), None);
+        assert_eq!(tag.version(), Version::Id3v24);
+
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,58 @@
+//!
+//! Test helpers writing audio files into the temp folder.
+//!
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Samples written as is into WAV files
+pub trait WavSample: hound::Sample + Copy {
+    const BITS_PER_SAMPLE: u16;
+    const FORMAT: hound::SampleFormat;
+}
+
+impl WavSample for i16 {
+    const BITS_PER_SAMPLE: u16 = 16;
+    const FORMAT: hound::SampleFormat = hound::SampleFormat::Int;
+}
+
+impl WavSample for f32 {
+    const BITS_PER_SAMPLE: u16 = 32;
+    const FORMAT: hound::SampleFormat = hound::SampleFormat::Float;
+}
+
+/// Path in the temp folder, unique to the test process
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("audio-player-{}-{}", std::process::id(), name))
+}
+
+/// Folder in the temp folder, unique to the test process
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = temp_path(name);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes stereo frames into a WAV file, 16 bits integers or 32 bits floats
+pub fn write_wav<S: WavSample>(path: &Path, rate: u32, frames: impl IntoIterator<Item = [S; 2]>) {
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: rate,
+        bits_per_sample: S::BITS_PER_SAMPLE,
+        sample_format: S::FORMAT,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    for frame in frames {
+        for sample in frame {
+            writer.write_sample(sample).unwrap();
+        }
+    }
+    writer.finalize().unwrap();
+}
+
+/// Stereo frames at 8 kHz, left sample is the frame index, right one its opposite
+pub fn write_ramp(name: &str, frames: i16) -> PathBuf {
+    let path = temp_path(name);
+    write_wav(&path, 8000, (0..frames).map(|frame| [frame, -frame]));
+    path
+}
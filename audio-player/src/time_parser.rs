@@ -0,0 +1,41 @@
+//!
+//! Times given on the command line, shared by the crates binaries.
+//!
+
+use std::time::Duration;
+
+/// Parses a time given as seconds or mm:ss i.e ["95", "1:35", "1:35.5"]
+pub fn parse_time(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid time '{}', expected seconds or mm:ss", value);
+    let seconds = match value.split_once(':') {
+        Some((mins, secs)) => {
+            let mins = mins.parse::<u64>().map_err(|_| invalid())?;
+            let secs = secs.parse::<f64>().map_err(|_| invalid())?;
+            if !(0.0..60.0).contains(&secs) {
+                return Err(invalid());
+            }
+            (mins * 60) as f64 + secs
+        }
+        None => value.parse::<f64>().map_err(|_| invalid())?,
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_seconds_and_minutes() {
+        assert_eq!(parse_time("95"), Ok(Duration::from_secs(95)));
+        assert_eq!(parse_time("2:30"), Ok(Duration::from_secs(150)));
+        assert_eq!(parse_time("0:01.5"), Ok(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn it_rejects_invalid_time() {
+        assert!(parse_time("-3").is_err());
+        assert!(parse_time("1:75").is_err());
+        assert!(parse_time("a:10").is_err());
+    }
+}